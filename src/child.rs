@@ -0,0 +1,154 @@
+use std::io;
+use std::mem::MaybeUninit;
+use std::os::unix::process::ExitStatusExt;
+use std::process::{Child, ExitStatus};
+#[cfg(target_os = "linux")]
+use std::time::{Duration, Instant};
+
+#[cfg(target_os = "linux")]
+use crate::{Error, ProcFs, ProcessStat};
+use crate::{ResourceUsage, Result};
+
+/// One reading of a running child's /proc/[pid]/stat.
+#[cfg(target_os = "linux")]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ChildSample {
+    pub time: Instant,
+    pub stat: ProcessStat,
+}
+
+/// Waits for a `std::process::Child` and reports its CPU usage like
+/// `time(1)` does.
+///
+/// The child is reaped with wait4(2) behind the back of `Child`, which
+/// would then still consider it running and could `kill()` an unrelated
+/// process that reused its PID. The methods therefore consume the `Child`.
+pub trait ChildExt {
+    /// Waits for the child to exit and returns its exit status together
+    /// with the resources it used.
+    fn wait_with_usage(self) -> Result<(ExitStatus, ResourceUsage)>;
+
+    /// Like `wait_with_usage()`, but reads the child's /proc/[pid]/stat
+    /// every `interval` while it runs.
+    #[cfg(target_os = "linux")]
+    fn wait_with_samples(
+        self,
+        interval: Duration,
+    ) -> Result<(ExitStatus, ResourceUsage, Vec<ChildSample>)>;
+}
+
+impl ChildExt for Child {
+    fn wait_with_usage(mut self) -> Result<(ExitStatus, ResourceUsage)> {
+        // Close stdin like Child::wait() does, so the child does not wait
+        // for input forever.
+        drop(self.stdin.take());
+
+        match wait4(self.id(), 0)? {
+            Some((status, usage)) => Ok((status, usage)),
+            None => {
+                let msg = "wait4 returned before the child exited";
+                Err(io::Error::other(msg).into())
+            }
+        }
+    }
+
+    #[cfg(target_os = "linux")]
+    fn wait_with_samples(
+        mut self,
+        interval: Duration,
+    ) -> Result<(ExitStatus, ResourceUsage, Vec<ChildSample>)> {
+        drop(self.stdin.take());
+
+        let procfs = ProcFs::default();
+        let mut samples = Vec::new();
+
+        loop {
+            if let Some((status, usage)) = wait4(self.id(), libc::WNOHANG)? {
+                return Ok((status, usage, samples));
+            }
+
+            // The child may exit between wait4() and reading its stat.
+            match procfs.process_stat(self.id()) {
+                Ok(stat) => samples.push(ChildSample {
+                    time: Instant::now(),
+                    stat,
+                }),
+                Err(Error::ProcessGone(_)) => (),
+                Err(err) => return Err(err),
+            }
+
+            std::thread::sleep(interval);
+        }
+    }
+}
+
+// Returns None if `options` has WNOHANG and the child is still running.
+fn wait4(pid: u32, options: libc::c_int) -> Result<Option<(ExitStatus, ResourceUsage)>> {
+    let mut status = 0;
+    let mut usage = MaybeUninit::<libc::rusage>::uninit();
+
+    loop {
+        let ret =
+            unsafe { libc::wait4(pid as libc::pid_t, &mut status, options, usage.as_mut_ptr()) };
+
+        match ret {
+            -1 => {
+                let err = io::Error::last_os_error();
+                if err.kind() != io::ErrorKind::Interrupted {
+                    return Err(err.into());
+                }
+            }
+            0 => return Ok(None),
+            _ => {
+                let usage = unsafe { usage.assume_init() };
+                return Ok(Some((
+                    ExitStatus::from_raw(status),
+                    ResourceUsage::from_rusage(&usage),
+                )));
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::process::Command;
+
+    use super::ChildExt;
+
+    #[test]
+    fn test_wait_with_usage() {
+        let child = Command::new("sh")
+            .args([
+                "-c",
+                "i=0; while [ $i -lt 20000 ]; do i=$((i+1)); done; exit 3",
+            ])
+            .spawn()
+            .unwrap();
+
+        let (status, usage) = child.wait_with_usage().unwrap();
+        assert_eq!(status.code(), Some(3));
+        assert!(!usage.total().is_zero());
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_wait_with_samples() {
+        use std::time::Duration;
+
+        // runs for well over the sampling interval
+        let child = Command::new("sh")
+            .args([
+                "-c",
+                "sleep 0.2; i=0; while [ $i -lt 20000 ]; do i=$((i+1)); done",
+            ])
+            .spawn()
+            .unwrap();
+        let pid = child.id();
+
+        let (status, _usage, samples) = child.wait_with_samples(Duration::from_millis(5)).unwrap();
+        assert!(status.success());
+        assert!(!samples.is_empty());
+        assert!(samples.iter().all(|sample| sample.stat.pid == pid));
+    }
+}
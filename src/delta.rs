@@ -0,0 +1,412 @@
+use std::collections::BTreeMap;
+use std::ops::{Add, Sub};
+use std::time::Duration;
+
+use crate::{Anomaly, CpuStats, Error, Result, SanitizePolicy, Sanitized};
+
+/// CPU time spent in each state between two `CpuStats` snapshots.
+///
+/// States the kernel did not report in both snapshots are `None`.
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq)]
+pub struct CpuDelta {
+    pub user: Duration,
+    pub nice: Duration,
+    pub system: Duration,
+    pub idle: Duration,
+    pub iowait: Option<Duration>,
+    pub irq: Option<Duration>,
+    pub softirq: Option<Duration>,
+    pub steal: Option<Duration>,
+    pub guest: Option<Duration>,
+    pub guest_nice: Option<Duration>,
+}
+
+/// Share of the interval spent in each state, between 0.0 and 1.0.
+#[derive(Debug, Copy, Clone, Default, PartialEq)]
+pub struct CpuUtilization {
+    pub user: f64,
+    pub nice: f64,
+    pub system: f64,
+    pub idle: f64,
+    pub iowait: Option<f64>,
+    pub irq: Option<f64>,
+    pub softirq: Option<f64>,
+    pub steal: Option<f64>,
+    pub guest: Option<f64>,
+    pub guest_nice: Option<f64>,
+    /// everything except `idle` and `iowait`
+    pub busy: f64,
+}
+
+/// A CPU that came online or went offline between two per-CPU snapshots.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub enum HotplugEvent {
+    /// the CPU is only in the later snapshot
+    Online(usize),
+    /// the CPU is only in the earlier snapshot
+    Offline(usize),
+}
+
+/// Per-CPU deltas between two snapshots such as `cpu_stats_per_cpu()`
+/// returns.
+///
+/// Offline CPUs have no counters, so only CPUs present in both snapshots
+/// get a delta. The others are reported in `events`.
+///
+/// Per-CPU counters go backwards now and then, iowait in particular, so
+/// each CPU is sanitized on its own and one bad CPU does not spoil the
+/// others.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct PerCpuDelta {
+    /// deltas of CPUs present in both snapshots, keyed by CPU id, without
+    /// the CPUs whose delta the policy dropped
+    pub cpus: BTreeMap<usize, CpuDelta>,
+    /// CPUs that came online or went offline, ordered by CPU id
+    pub events: Vec<HotplugEvent>,
+    /// anomalies found in the counters of each CPU, keyed by CPU id
+    pub anomalies: BTreeMap<usize, Vec<Anomaly>>,
+}
+
+impl CpuDelta {
+    /// Computes `later - earlier`.
+    ///
+    /// Returns `Error::CounterRegression` if any counter in `later` is
+    /// smaller than in `earlier`.
+    pub fn between(earlier: &CpuStats, later: &CpuStats) -> Result<CpuDelta> {
+        Ok(CpuDelta {
+            user: sub("user", later.user, earlier.user)?,
+            nice: sub("nice", later.nice, earlier.nice)?,
+            system: sub("system", later.system, earlier.system)?,
+            idle: sub("idle", later.idle, earlier.idle)?,
+            iowait: sub_opt("iowait", later.iowait, earlier.iowait)?,
+            irq: sub_opt("irq", later.irq, earlier.irq)?,
+            softirq: sub_opt("softirq", later.softirq, earlier.softirq)?,
+            steal: sub_opt("steal", later.steal, earlier.steal)?,
+            guest: sub_opt("guest", later.guest, earlier.guest)?,
+            guest_nice: sub_opt("guest_nice", later.guest_nice, earlier.guest_nice)?,
+        })
+    }
+
+    /// Total CPU time elapsed over the interval.
+    ///
+    /// Guest time is already part of `user` and `nice` and not added again.
+    pub fn total(&self) -> Duration {
+        self.user
+            + self.nice
+            + self.system
+            + self.idle
+            + self.iowait.unwrap_or_default()
+            + self.irq.unwrap_or_default()
+            + self.softirq.unwrap_or_default()
+            + self.steal.unwrap_or_default()
+    }
+
+    /// CPU time spent doing something else than idling or waiting for I/O.
+    pub fn busy(&self) -> Duration {
+        self.total() - self.idle - self.iowait.unwrap_or_default()
+    }
+
+    /// Returns the fraction of `total()` spent in each state.
+    ///
+    /// All fractions are zero if no time elapsed.
+    pub fn utilization(&self) -> CpuUtilization {
+        let total = self.total();
+        let fraction = |d: Duration| {
+            if total.is_zero() {
+                0.0
+            } else {
+                d.as_secs_f64() / total.as_secs_f64()
+            }
+        };
+
+        CpuUtilization {
+            user: fraction(self.user),
+            nice: fraction(self.nice),
+            system: fraction(self.system),
+            idle: fraction(self.idle),
+            iowait: self.iowait.map(fraction),
+            irq: self.irq.map(fraction),
+            softirq: self.softirq.map(fraction),
+            steal: self.steal.map(fraction),
+            guest: self.guest.map(fraction),
+            guest_nice: self.guest_nice.map(fraction),
+            busy: fraction(self.busy()),
+        }
+    }
+}
+
+impl Sub for CpuStats {
+    type Output = Result<CpuDelta>;
+
+    fn sub(self, earlier: CpuStats) -> Result<CpuDelta> {
+        CpuDelta::between(&earlier, &self)
+    }
+}
+
+impl Add for CpuDelta {
+    type Output = CpuDelta;
+
+    /// Sums two consecutive deltas. States missing from either are `None`.
+    fn add(self, other: CpuDelta) -> CpuDelta {
+        let add_opt = |a: Option<Duration>, b: Option<Duration>| Some(a? + b?);
+
+        CpuDelta {
+            user: self.user + other.user,
+            nice: self.nice + other.nice,
+            system: self.system + other.system,
+            idle: self.idle + other.idle,
+            iowait: add_opt(self.iowait, other.iowait),
+            irq: add_opt(self.irq, other.irq),
+            softirq: add_opt(self.softirq, other.softirq),
+            steal: add_opt(self.steal, other.steal),
+            guest: add_opt(self.guest, other.guest),
+            guest_nice: add_opt(self.guest_nice, other.guest_nice),
+        }
+    }
+}
+
+impl PerCpuDelta {
+    /// Computes `later - earlier` for each CPU present in both, handling
+    /// counters that went backwards according to `policy`.
+    pub fn between(
+        earlier: &BTreeMap<usize, CpuStats>,
+        later: &BTreeMap<usize, CpuStats>,
+        policy: &SanitizePolicy,
+    ) -> PerCpuDelta {
+        PerCpuDelta::compare(earlier, later, |earlier, later| {
+            policy.sanitize(earlier, later)
+        })
+    }
+
+    /// Like `between()`, but also uses the boot times of the snapshots, as
+    /// found in `ProcStat::btime`, so that a reboot in between is reported
+    /// as `Anomaly::Reset` instead of a replaced CPU.
+    pub fn between_with_btime(
+        earlier: &BTreeMap<usize, CpuStats>,
+        earlier_btime: u64,
+        later: &BTreeMap<usize, CpuStats>,
+        later_btime: u64,
+        policy: &SanitizePolicy,
+    ) -> PerCpuDelta {
+        PerCpuDelta::compare(earlier, later, |earlier, later| {
+            policy.sanitize_with_btime(earlier, earlier_btime, later, later_btime)
+        })
+    }
+
+    fn compare<F>(
+        earlier: &BTreeMap<usize, CpuStats>,
+        later: &BTreeMap<usize, CpuStats>,
+        mut sanitize: F,
+    ) -> PerCpuDelta
+    where
+        F: FnMut(&CpuStats, &CpuStats) -> Sanitized,
+    {
+        let mut delta = PerCpuDelta::default();
+
+        for (&cpu, earlier_stats) in earlier {
+            match later.get(&cpu) {
+                Some(later_stats) => {
+                    let sanitized = sanitize(earlier_stats, later_stats);
+                    if let Some(cpu_delta) = sanitized.delta {
+                        delta.cpus.insert(cpu, cpu_delta);
+                    }
+                    if !sanitized.anomalies.is_empty() {
+                        delta.anomalies.insert(cpu, sanitized.anomalies);
+                    }
+                }
+                None => delta.events.push(HotplugEvent::Offline(cpu)),
+            }
+        }
+        for &cpu in later.keys() {
+            if !earlier.contains_key(&cpu) {
+                delta.events.push(HotplugEvent::Online(cpu));
+            }
+        }
+
+        delta.events.sort_by_key(|event| match *event {
+            HotplugEvent::Online(cpu) | HotplugEvent::Offline(cpu) => cpu,
+        });
+        delta
+    }
+
+    /// Sum of the deltas of the CPUs present in both snapshots.
+    ///
+    /// Unlike the delta of the aggregate `cpu` line this does not mix in
+    /// time from CPUs that were online for only part of the interval.
+    pub fn total(&self) -> Option<CpuDelta> {
+        self.cpus.values().copied().reduce(|sum, delta| sum + delta)
+    }
+}
+
+/// Computes `later - earlier` of a cumulative counter.
+///
+/// Returns `Error::CounterRegression` naming `field` if it went backwards.
+pub(crate) fn sub<T: CheckedSub>(field: &'static str, later: T, earlier: T) -> Result<T> {
+    later
+        .checked_sub(earlier)
+        .ok_or(Error::CounterRegression { field })
+}
+
+/// Like `sub()` for counters older kernels may not have, `None` unless
+/// present in both snapshots.
+pub(crate) fn sub_opt<T: CheckedSub>(
+    field: &'static str,
+    later: Option<T>,
+    earlier: Option<T>,
+) -> Result<Option<T>> {
+    match (later, earlier) {
+        (Some(later), Some(earlier)) => sub(field, later, earlier).map(Some),
+        _ => Ok(None),
+    }
+}
+
+/// Counters that can be subtracted without underflowing.
+pub(crate) trait CheckedSub: Sized {
+    fn checked_sub(self, other: Self) -> Option<Self>;
+}
+
+impl CheckedSub for u64 {
+    fn checked_sub(self, other: Self) -> Option<Self> {
+        u64::checked_sub(self, other)
+    }
+}
+
+impl CheckedSub for Duration {
+    fn checked_sub(self, other: Self) -> Option<Self> {
+        Duration::checked_sub(self, other)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+    use std::time::Duration;
+
+    use super::{HotplugEvent, PerCpuDelta};
+    use crate::{Anomaly, CpuStats, Error, SanitizePolicy};
+
+    fn stats(user: u64, system: u64, idle: u64, iowait: Option<u64>) -> CpuStats {
+        CpuStats {
+            user: Duration::from_secs(user),
+            system: Duration::from_secs(system),
+            idle: Duration::from_secs(idle),
+            iowait: iowait.map(Duration::from_secs),
+            ..CpuStats::default()
+        }
+    }
+
+    #[test]
+    fn test_delta() {
+        let earlier = stats(10, 10, 10, Some(10));
+        let later = stats(16, 12, 11, Some(11));
+
+        let delta = (later - earlier).unwrap();
+        assert_eq!(delta.user, Duration::from_secs(6));
+        assert_eq!(delta.total(), Duration::from_secs(10));
+        assert_eq!(delta.busy(), Duration::from_secs(8));
+
+        let util = delta.utilization();
+        assert_eq!(util.user, 0.6);
+        assert_eq!(util.iowait, Some(0.1));
+        assert_eq!(util.irq, None);
+        assert_eq!(util.busy, 0.8);
+    }
+
+    #[test]
+    fn test_delta_missing_column() {
+        let earlier = stats(10, 10, 10, None);
+        let later = stats(10, 10, 10, Some(10));
+
+        let delta = (later - earlier).unwrap();
+        assert_eq!(delta.iowait, None);
+        assert_eq!(delta.utilization().busy, 0.0);
+    }
+
+    #[test]
+    fn test_delta_regression() {
+        let earlier = stats(10, 10, 10, Some(10));
+        let later = stats(10, 10, 10, Some(9));
+
+        match later - earlier {
+            Err(Error::CounterRegression { field: "iowait" }) => (),
+            other => panic!("unexpected {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_per_cpu_hotplug() {
+        let earlier = BTreeMap::from([
+            (0, stats(10, 10, 10, None)),
+            (1, stats(10, 10, 10, None)),
+            (2, stats(10, 10, 10, None)),
+        ]);
+        let later = BTreeMap::from([
+            (0, stats(12, 10, 12, None)),
+            (2, stats(11, 11, 12, None)),
+            (3, stats(1, 1, 1, None)),
+        ]);
+
+        let delta = PerCpuDelta::between(&earlier, &later, &SanitizePolicy::default());
+        assert_eq!(delta.cpus.keys().copied().collect::<Vec<_>>(), vec![0, 2]);
+        assert_eq!(
+            delta.events,
+            vec![HotplugEvent::Offline(1), HotplugEvent::Online(3)]
+        );
+
+        let total = delta.total().unwrap();
+        assert_eq!(total.busy(), Duration::from_secs(4));
+        assert_eq!(total.idle, Duration::from_secs(4));
+
+        assert!(delta.anomalies.is_empty());
+        assert_eq!(PerCpuDelta::default().total(), None);
+    }
+
+    #[test]
+    fn test_per_cpu_regression() {
+        let earlier = BTreeMap::from([
+            (0, stats(10, 10, 10, Some(10))),
+            (1, stats(10, 10, 10, Some(10))),
+            (2, stats(10, 10, 10, Some(10))),
+        ]);
+        let later = BTreeMap::from([
+            (0, stats(12, 10, 12, Some(11))),
+            (1, stats(12, 10, 18, Some(9))),
+            (2, stats(12, 10, 18, Some(5))),
+        ]);
+
+        // iowait of CPU 1 is clamped and CPU 2 dropped, CPU 0 is unaffected
+        let delta = PerCpuDelta::between(&earlier, &later, &SanitizePolicy::default());
+        assert_eq!(delta.cpus.keys().copied().collect::<Vec<_>>(), vec![0, 1]);
+        assert_eq!(delta.cpus[&1].iowait, Some(Duration::ZERO));
+        assert_eq!(
+            delta.anomalies.keys().copied().collect::<Vec<_>>(),
+            vec![1, 2]
+        );
+        assert_eq!(
+            delta.anomalies[&2],
+            vec![Anomaly::Regression {
+                field: "iowait",
+                by: Duration::from_secs(5)
+            }]
+        );
+    }
+
+    #[test]
+    fn test_per_cpu_reset() {
+        let earlier = BTreeMap::from([(0, stats(90, 90, 90, None)), (1, stats(90, 90, 90, None))]);
+        let later = BTreeMap::from([(0, stats(1, 1, 1, None)), (1, stats(1, 1, 1, None))]);
+        let reset = Anomaly::Reset {
+            earlier_btime: 1000,
+            later_btime: 1200,
+        };
+
+        let policy = SanitizePolicy::default();
+        let delta = PerCpuDelta::between_with_btime(&earlier, 1000, &later, 1200, &policy);
+        assert!(delta.cpus.is_empty());
+        assert_eq!(delta.anomalies[&0], vec![reset]);
+        assert_eq!(delta.anomalies[&1], vec![reset]);
+
+        let delta = PerCpuDelta::between(&earlier, &later, &policy);
+        assert_eq!(delta.anomalies[&0], vec![Anomaly::CpuReplaced]);
+    }
+}
@@ -0,0 +1,86 @@
+use std::fmt;
+use std::io;
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+/// Errors returned when reading CPU statistics.
+///
+/// Line and column numbers start from 1. Columns count whitespace separated
+/// fields, not characters.
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum Error {
+    /// reading from the kernel failed
+    Io(io::Error),
+    /// a field could not be parsed
+    Malformed {
+        line: usize,
+        column: usize,
+        token: String,
+    },
+    /// a line ended before a required field
+    MissingField {
+        line: usize,
+        column: usize,
+        field: &'static str,
+    },
+    /// sysconf(_SC_CLK_TCK) returned something other than a positive number
+    ClockTicks(i64),
+    /// a cumulative counter was smaller in the later snapshot
+    CounterRegression { field: &'static str },
+    /// the kernel does not provide pressure stall information
+    PressureUnavailable,
+    /// no cgroup hierarchy with CPU accounting is mounted
+    CgroupNotMounted,
+    /// the process or thread with this id does not exist or exited
+    ProcessGone(u32),
+    /// an argument was out of range, with a description of why
+    InvalidArgument(&'static str),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Io(err) => write!(f, "I/O error: {}", err),
+            Error::Malformed {
+                line,
+                column,
+                token,
+            } => write!(
+                f,
+                "malformed value {:?} at line {}, column {}",
+                token, line, column
+            ),
+            Error::MissingField {
+                line,
+                column,
+                field,
+            } => write!(
+                f,
+                "missing field {} at line {}, column {}",
+                field, line, column
+            ),
+            Error::ClockTicks(ticks) => write!(f, "invalid clock tick rate {}", ticks),
+            Error::CounterRegression { field } => write!(f, "counter {} went backwards", field),
+            Error::PressureUnavailable => write!(f, "pressure stall information is not available"),
+            Error::CgroupNotMounted => write!(f, "no cgroup hierarchy is mounted"),
+            Error::ProcessGone(pid) => write!(f, "process {} is gone", pid),
+            Error::InvalidArgument(why) => write!(f, "invalid argument: {}", why),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Io(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for Error {
+    fn from(err: io::Error) -> Self {
+        Error::Io(err)
+    }
+}
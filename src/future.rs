@@ -0,0 +1,125 @@
+use std::future::Future;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use std::time::Duration;
+
+use crate::thread_cpu_time;
+
+/// Future that measures the thread CPU time spent polling the inner future.
+///
+/// Only the time spent inside `poll` is counted, so tasks sharing an
+/// executor thread are not charged for each other. Works with any executor.
+#[derive(Debug)]
+#[must_use = "futures do nothing unless polled"]
+pub struct CpuTimed<F> {
+    inner: F,
+    cpu: Duration,
+    polls: u64,
+}
+
+/// Adds `cpu_timed()` to every future.
+pub trait CpuTimedExt: Future + Sized {
+    /// Wraps the future so that it resolves to its output together with
+    /// the CPU time spent polling it.
+    fn cpu_timed(self) -> CpuTimed<Self> {
+        CpuTimed::new(self)
+    }
+}
+
+impl<F: Future> CpuTimedExt for F {}
+
+impl<F> CpuTimed<F> {
+    pub fn new(inner: F) -> Self {
+        CpuTimed {
+            inner,
+            cpu: Duration::ZERO,
+            polls: 0,
+        }
+    }
+
+    /// CPU time spent polling so far.
+    pub fn cpu_time(&self) -> Duration {
+        self.cpu
+    }
+
+    /// Number of times the future has been polled.
+    pub fn polls(&self) -> u64 {
+        self.polls
+    }
+}
+
+impl<F: Future> Future for CpuTimed<F> {
+    type Output = (F::Output, Duration);
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        // SAFETY: `inner` is never moved out of the pinned `CpuTimed`, which
+        // has no Drop impl, and the other fields are not pinned.
+        let this = unsafe { self.get_unchecked_mut() };
+        let inner = unsafe { Pin::new_unchecked(&mut this.inner) };
+
+        // If the clock cannot be read the poll still goes ahead, it is just
+        // not counted.
+        let start = thread_cpu_time();
+        let poll = inner.poll(cx);
+        if let (Ok(start), Ok(end)) = (start, thread_cpu_time()) {
+            this.cpu += end.saturating_sub(start);
+        }
+        this.polls += 1;
+
+        poll.map(|output| (output, this.cpu))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::future::Future;
+    use std::pin::{pin, Pin};
+    use std::task::{Context, Poll, Waker};
+    use std::time::Duration;
+
+    use super::CpuTimedExt;
+
+    /// Spins on every poll and finishes after `remaining` polls.
+    struct Busy {
+        remaining: u32,
+    }
+
+    impl Future for Busy {
+        type Output = u32;
+
+        fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<u32> {
+            let mut x: u64 = 0;
+            for i in 0..5_000_000u64 {
+                x = std::hint::black_box(x.wrapping_add(i));
+            }
+
+            self.remaining -= 1;
+            if self.remaining == 0 {
+                Poll::Ready(42)
+            } else {
+                cx.waker().wake_by_ref();
+                Poll::Pending
+            }
+        }
+    }
+
+    #[test]
+    fn test_cpu_timed() {
+        let mut cx = Context::from_waker(Waker::noop());
+        let mut future = pin!(Busy { remaining: 3 }.cpu_timed());
+
+        assert!(future.as_mut().poll(&mut cx).is_pending());
+        assert!(future.as_mut().poll(&mut cx).is_pending());
+        let after_two = future.cpu_time();
+        assert_eq!(future.polls(), 2);
+
+        match future.as_mut().poll(&mut cx) {
+            Poll::Ready((output, cpu)) => {
+                assert_eq!(output, 42);
+                assert!(cpu > after_two);
+                assert!(cpu > Duration::ZERO);
+            }
+            Poll::Pending => panic!("future should have finished"),
+        }
+    }
+}
@@ -1,192 +1,164 @@
+use std::ops::Add;
 use std::time::Duration;
 
-#[derive(Debug, Copy, Clone, Default)]
+pub use child::ChildExt;
+#[cfg(target_os = "linux")]
+pub use child::ChildSample;
+pub use delta::{CpuDelta, CpuUtilization, HotplugEvent, PerCpuDelta};
+pub use error::{Error, Result};
+pub use future::{CpuTimed, CpuTimedExt};
+pub use rusage::{process_cpu_time, resource_usage, thread_cpu_time, ResourceUsage, UsageOf};
+pub use sampler::{Sample, Sampler, SamplerHandle};
+pub use sanitize::{Anomaly, SanitizePolicy, Sanitized};
+pub use timer::{measure, CpuCounter, CpuCounters, CpuMeasurement, CpuTimer};
+
+mod child;
+mod delta;
+mod error;
+mod future;
+mod rusage;
+mod sampler;
+mod sanitize;
+mod sync;
+#[cfg(test)]
+mod test_util;
+mod timer;
+
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq)]
 pub struct CpuStats {
     /// normal processes executing in user mode
     pub user: Duration,
+    /// niced processes executing in user mode
+    pub nice: Duration,
     /// processes executing in kernel mode
     pub system: Duration,
     /// system twiddling thumbs
     pub idle: Duration,
+    /// waiting for I/O to complete (Linux 2.5.41+)
+    pub iowait: Option<Duration>,
+    /// servicing interrupts (Linux 2.6.0+)
+    pub irq: Option<Duration>,
+    /// servicing softirqs (Linux 2.6.0+)
+    pub softirq: Option<Duration>,
+    /// involuntary wait while the hypervisor was servicing another guest
+    /// (Linux 2.6.11+)
+    pub steal: Option<Duration>,
+    /// running a virtual CPU for a guest, already included in `user`
+    /// (Linux 2.6.24+)
+    pub guest: Option<Duration>,
+    /// running a niced guest, already included in `nice` (Linux 2.6.33+)
+    pub guest_nice: Option<Duration>,
 }
 
-#[cfg(target_os = "macos")]
-pub use macos::cpu_stats;
-
-#[cfg(target_os = "macos")]
-mod macos {
-    use std::io;
-    use std::mem::MaybeUninit;
-    use std::time::Duration;
-
-    use crate::{clock_ticks, CpuStats};
-
-    pub fn cpu_stats() -> io::Result<crate::CpuStats> {
-        let host_port = get_host_port();
-        let processor_info = get_host_processor_info(host_port)?;
-        deallocate_host_port(host_port)?;
-
-        let mut user_total: usize = 0;
-        let mut system_total: usize = 0;
-        let mut idle_total: usize = 0;
-
-        for (user, system, idle, _nice) in processor_info {
-            user_total += user;
-            system_total += system;
-            idle_total += idle;
-        }
-
-        let cpu_stats = CpuStats {
-            user: Duration::from_secs(user_total as u64) / clock_ticks() as u32,
-            system: Duration::from_secs(system_total as u64) / clock_ticks() as u32,
-            idle: Duration::from_secs(idle_total as u64) / clock_ticks() as u32,
-        };
-
-        Ok(cpu_stats)
+impl CpuStats {
+    /// Total accounted CPU time.
+    ///
+    /// The kernel already counts `guest` and `guest_nice` into `user` and
+    /// `nice`, so they are not added a second time here.
+    pub fn total(&self) -> Duration {
+        self.user
+            + self.nice
+            + self.system
+            + self.idle
+            + self.iowait.unwrap_or_default()
+            + self.irq.unwrap_or_default()
+            + self.softirq.unwrap_or_default()
+            + self.steal.unwrap_or_default()
     }
 
-    fn get_host_port() -> libc::mach_port_t {
-        unsafe { libc::mach_host_self() }
+    /// Time spent in user mode excluding time spent running guests.
+    pub fn user_without_guest(&self) -> Duration {
+        self.user.saturating_sub(self.guest.unwrap_or_default())
     }
 
-    fn deallocate_host_port(name: libc::mach_port_t) -> io::Result<()> {
-        let ret = unsafe { mach2::mach_port::mach_port_deallocate(libc::mach_task_self(), name) };
-        if ret == -1 {
-            return Err(io::Error::last_os_error());
-        }
-        Ok(())
+    /// Time spent in niced user mode excluding time spent running niced
+    /// guests.
+    pub fn nice_without_guest(&self) -> Duration {
+        self.nice
+            .saturating_sub(self.guest_nice.unwrap_or_default())
     }
+}
 
-    fn get_host_processor_info(
-        host: libc::mach_port_t,
-    ) -> io::Result<Vec<(usize, usize, usize, usize)>> {
-        let mut cpu_count: libc::natural_t = 0;
-        let mut cpu_info: MaybeUninit<libc::processor_info_array_t> = MaybeUninit::uninit();
-        let mut cpu_info_count = 0;
-
-        let ret = unsafe {
-            libc::host_processor_info(
-                host,
-                2,
-                &mut cpu_count,
-                cpu_info.as_mut_ptr(),
-                &mut cpu_info_count,
-            )
-        };
-
-        if ret == -1 {
-            return Err(io::Error::last_os_error());
-        }
-
-        let cpu_info = unsafe { cpu_info.assume_init() };
-
-        let cpu_info_slice =
-            unsafe { std::slice::from_raw_parts(cpu_info, cpu_info_count as usize) };
-
-        let mut array = Vec::new();
-        for chunk in cpu_info_slice.chunks(4) {
-            array.push((
-                chunk[0] as usize,
-                chunk[1] as usize,
-                chunk[2] as usize,
-                chunk[3] as usize,
-            ));
-        }
-
-        let ret = unsafe {
-            libc::vm_deallocate(
-                libc::mach_task_self(),
-                cpu_info as libc::vm_address_t,
-                cpu_info_count as libc::vm_size_t,
-            )
-        };
-
-        if ret == -1 {
-            return Err(io::Error::last_os_error());
+impl Add for CpuStats {
+    type Output = CpuStats;
+
+    /// Sums the time of two CPUs. States missing from either are `None`.
+    fn add(self, other: CpuStats) -> CpuStats {
+        let add_opt = |a: Option<Duration>, b: Option<Duration>| Some(a? + b?);
+
+        CpuStats {
+            user: self.user + other.user,
+            nice: self.nice + other.nice,
+            system: self.system + other.system,
+            idle: self.idle + other.idle,
+            iowait: add_opt(self.iowait, other.iowait),
+            irq: add_opt(self.irq, other.irq),
+            softirq: add_opt(self.softirq, other.softirq),
+            steal: add_opt(self.steal, other.steal),
+            guest: add_opt(self.guest, other.guest),
+            guest_nice: add_opt(self.guest_nice, other.guest_nice),
         }
-
-        Ok(array)
     }
 }
 
-#[cfg(target_os = "linux")]
-pub use linux::read_proc_stat_cpu as cpu_stats;
-
-#[cfg(target_os = "linux")]
-mod linux {
-    use std::io::{self, BufRead, BufReader};
-    use std::time::Duration;
-
-    use crate::{clock_ticks, CpuStats};
-
-    // https://www.linuxhowtos.org/System/procstat.htm
-    pub fn read_proc_stat_cpu() -> io::Result<crate::CpuStats> {
-        let mut fd = BufReader::new(std::fs::File::open("/proc/stat")?);
-
-        let mut line = String::new();
-        let _len = fd.read_line(&mut line)?;
-
-        let mut stats = CpuStats::default();
+#[cfg(target_os = "macos")]
+pub use macos::{cpu_stats, cpu_stats_per_cpu};
 
-        for (i, v) in line.split_ascii_whitespace().enumerate() {
-            match i {
-                0 => (),
-                1 => stats.user = parse_to_duration(v),
-                2 => (),
-                3 => stats.system = parse_to_duration(v),
-                4 => stats.idle = parse_to_duration(v),
-                _ => break,
-            }
-        }
+#[cfg(target_os = "macos")]
+mod macos;
 
-        Ok(stats)
-    }
+#[cfg(target_os = "linux")]
+pub use linux::{
+    node_utilization, read_cgroup_cpu_stats as cgroup_cpu_stats,
+    read_cpufreq_per_cpu as cpu_frequencies, read_effective_cpus as effective_cpus,
+    read_loadavg as load_average, read_numa_nodes as numa_nodes, read_online_cpus as online_cpus,
+    read_pressure_cpu as cpu_pressure, read_proc_stat as proc_stat,
+    read_proc_stat_cpu as cpu_stats, read_proc_stat_per_cpu as cpu_stats_per_cpu,
+    read_process_stat as process_stat, read_threads as threads, read_topology as topology, Cgroup,
+    CgroupCpuDelta, CgroupCpuStats, CgroupFs, CgroupVersion, CoreUtilization, CpuFreq, CpuLimit,
+    CpuQuota, CpuSet, CpuTopology, EffectiveCpus, Interrupts, LoadAvg, NumaNode, Pressure,
+    PressureDelta, PressureLine, ProcFs, ProcStat, ProcessStat, SoftIrqs, SysFs, ThreadDelta,
+    ThreadStat, ThreadUsage, TimeInState, Topology,
+};
 
-    fn parse_to_duration(v: &str) -> Duration {
-        let v = v.parse().unwrap();
-        let d1 = Duration::from_secs(v);
-        d1 / clock_ticks() as u32
-    }
-}
+#[cfg(target_os = "linux")]
+mod linux;
 
 pub use clock_ticks::clock_ticks;
 
 mod clock_ticks {
-    use std::io;
-    use std::sync::Once;
+    use std::sync::OnceLock;
+    use std::time::Duration;
 
-    static mut CLOCK_TICKS: usize = 0;
-    static CLOCK_TICKS_INIT: Once = Once::new();
+    use crate::{Error, Result};
 
-    /// Returns the number of CPU clock ticks per second.
-    pub fn clock_ticks() -> usize {
-        unsafe {
-            CLOCK_TICKS_INIT.call_once(|| {
-                CLOCK_TICKS = sysconf_clock_ticks().unwrap();
-            });
+    static CLOCK_TICKS: OnceLock<libc::c_long> = OnceLock::new();
 
-            CLOCK_TICKS
+    /// Returns the number of CPU clock ticks per second.
+    pub fn clock_ticks() -> Result<usize> {
+        let ticks = *CLOCK_TICKS.get_or_init(|| unsafe { libc::sysconf(libc::_SC_CLK_TCK) });
+
+        match u32::try_from(ticks) {
+            Ok(ticks) if ticks > 0 => Ok(ticks as usize),
+            // c_long is only 32 bits wide on some targets
+            #[allow(clippy::useless_conversion)]
+            _ => Err(Error::ClockTicks(ticks.into())),
         }
     }
 
-    fn sysconf_clock_ticks() -> io::Result<usize> {
-        let ret = unsafe { libc::sysconf(libc::_SC_CLK_TCK) };
-
-        if ret == -1 {
-            return Err(io::Error::last_os_error());
-        }
-
-        Ok(ret as usize)
+    /// Converts a count of clock ticks to a `Duration`.
+    pub(crate) fn ticks_to_duration(ticks: u64) -> Result<Duration> {
+        Ok(Duration::from_secs(ticks) / clock_ticks()? as u32)
     }
 }
 
 #[cfg(test)]
 mod tests {
-    use crate::{clock_ticks, cpu_stats};
+    use crate::{clock_ticks, cpu_stats, cpu_stats_per_cpu};
 
     #[test]
     fn test_clock_ticks() {
-        let ticks = clock_ticks();
+        let ticks = clock_ticks().unwrap();
         assert!(ticks > 0);
     }
 
@@ -197,4 +169,40 @@ mod tests {
         assert!(!stats.system.is_zero());
         assert!(!stats.idle.is_zero());
     }
+
+    #[test]
+    fn test_cpu_stats_per_cpu() {
+        let cpus = cpu_stats_per_cpu().unwrap();
+        assert!(!cpus.is_empty());
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_proc_stat() {
+        let stat = crate::proc_stat().unwrap();
+        assert!(stat.btime > 0);
+        assert!(stat.processes > 0);
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_load_average() {
+        let load = crate::load_average().unwrap();
+        assert!(load.total > 0);
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_cgroup_cpu_stats() {
+        let stats = crate::cgroup_cpu_stats().unwrap();
+        assert!(!stats.usage.is_zero());
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_effective_cpus() {
+        let cpus = crate::effective_cpus().unwrap();
+        assert!(cpus.cpus > 0.0);
+        assert!(cpus.cpus <= cpus.online as f64);
+    }
 }
@@ -0,0 +1,59 @@
+use std::io;
+use std::mem;
+
+use crate::{CpuSet, Result, SysFs};
+
+/// Returns the CPUs the calling thread may run on.
+///
+/// `libc::cpu_set_t` only fits 1024 CPUs and the kernel rejects masks
+/// smaller than the number of possible CPUs with EINVAL, so the mask is
+/// sized from the possible CPUs and grown until the kernel accepts it.
+pub(crate) fn sched_getaffinity() -> Result<CpuSet> {
+    const WORD_BITS: usize = mem::size_of::<libc::c_ulong>() * 8;
+
+    let possible = SysFs::default()
+        .possible_cpus()
+        .ok()
+        .and_then(|cpus| cpus.iter().last())
+        .map_or(0, |last| last + 1);
+    let mut bits = possible.max(libc::CPU_SETSIZE as usize);
+
+    loop {
+        let mut mask: Vec<libc::c_ulong> = vec![0; bits.div_ceil(WORD_BITS)];
+        let size = mask.len() * mem::size_of::<libc::c_ulong>();
+
+        // cpu_set_t is itself an array of c_ulong, so a longer array has
+        // the same layout.
+        let ret =
+            unsafe { libc::sched_getaffinity(0, size, mask.as_mut_ptr() as *mut libc::cpu_set_t) };
+        if ret == -1 {
+            let err = io::Error::last_os_error();
+            if err.raw_os_error() == Some(libc::EINVAL) && bits < CpuSet::LIMIT {
+                bits *= 2;
+                continue;
+            }
+            return Err(err.into());
+        }
+
+        let cpus = mask
+            .iter()
+            .enumerate()
+            .flat_map(|(i, &word)| {
+                (0..WORD_BITS)
+                    .filter(move |bit| word & (1 << bit) != 0)
+                    .map(move |bit| i * WORD_BITS + bit)
+            })
+            .collect();
+        return Ok(cpus);
+    }
+}
+
+/// Returns the number of CPUs currently online.
+pub(crate) fn online_cpus() -> Result<usize> {
+    let ret = unsafe { libc::sysconf(libc::_SC_NPROCESSORS_ONLN) };
+    if ret == -1 {
+        return Err(io::Error::last_os_error().into());
+    }
+
+    Ok(ret as usize)
+}
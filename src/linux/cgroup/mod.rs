@@ -0,0 +1,452 @@
+use std::io;
+use std::ops::Sub;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use self::mount::{Membership, Mount};
+use crate::delta::{sub, sub_opt};
+use crate::linux::parse::{not_found_as_none, open};
+use crate::{CpuSet, Error, ProcFs, Result};
+
+mod mount;
+mod v1;
+mod v2;
+
+/// CPU accounting of a cgroup.
+///
+/// Throttling counters are `None` when the cgroup has no CPU bandwidth
+/// controller, and burst counters when the kernel predates them.
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq)]
+pub struct CgroupCpuStats {
+    /// total CPU time used by tasks in the cgroup
+    pub usage: Duration,
+    /// CPU time spent in user mode
+    pub user: Duration,
+    /// CPU time spent in kernel mode
+    pub system: Duration,
+    /// enforcement periods that have elapsed
+    pub nr_periods: Option<u64>,
+    /// periods in which the cgroup was throttled
+    pub nr_throttled: Option<u64>,
+    /// total time the cgroup was throttled
+    pub throttled: Option<Duration>,
+    /// periods in which the cgroup used its burst allowance
+    pub nr_bursts: Option<u64>,
+    /// total time spent bursting over the quota
+    pub burst: Option<Duration>,
+}
+
+/// Change in a cgroup's CPU accounting between two snapshots.
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq)]
+pub struct CgroupCpuDelta {
+    pub usage: Duration,
+    pub user: Duration,
+    pub system: Duration,
+    pub nr_periods: Option<u64>,
+    pub nr_throttled: Option<u64>,
+    pub throttled: Option<Duration>,
+    pub nr_bursts: Option<u64>,
+    pub burst: Option<Duration>,
+}
+
+/// CPU bandwidth limit of a cgroup: `quota` of CPU time every `period`.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct CpuQuota {
+    pub quota: Duration,
+    pub period: Duration,
+}
+
+impl CpuQuota {
+    /// Number of CPUs worth of time the quota allows.
+    pub fn cpus(&self) -> f64 {
+        self.quota.as_nanos() as f64 / self.period.as_nanos() as f64
+    }
+}
+
+/// Version of a cgroup hierarchy.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum CgroupVersion {
+    V1,
+    V2,
+}
+
+/// The mounted cgroup hierarchy to read CPU accounting from.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CgroupFs {
+    hierarchy: Hierarchy,
+}
+
+/// A single cgroup.
+///
+/// With cgroup v1 the same cgroup has a directory under each controller's
+/// mount.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Cgroup {
+    dirs: Dirs,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Hierarchy {
+    V1(Vec<Mount>),
+    V2(Mount),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Dirs {
+    V1(Vec<(Vec<String>, PathBuf)>),
+    V2(PathBuf),
+}
+
+impl CgroupFs {
+    /// Cgroup v2 hierarchy mounted at `root`, usually `/sys/fs/cgroup`.
+    pub fn new(root: impl Into<PathBuf>) -> Self {
+        CgroupFs {
+            hierarchy: Hierarchy::V2(Mount {
+                controllers: Vec::new(),
+                root: PathBuf::from("/"),
+                mount_point: root.into(),
+            }),
+        }
+    }
+
+    /// Cgroup v1 hierarchies with each controller mounted in its own
+    /// directory under `root`, usually `/sys/fs/cgroup`.
+    pub fn new_v1(root: impl Into<PathBuf>) -> Self {
+        let root = root.into();
+        let mounts = ["cpu", "cpuacct", "cpuset"]
+            .iter()
+            .map(|controller| Mount {
+                controllers: vec![controller.to_string()],
+                root: PathBuf::from("/"),
+                mount_point: root.join(controller),
+            })
+            .collect();
+
+        CgroupFs {
+            hierarchy: Hierarchy::V1(mounts),
+        }
+    }
+
+    /// Finds the cgroup mounts from `self/mountinfo` of `procfs`.
+    ///
+    /// The v1 hierarchy is preferred if the cpuacct controller is attached
+    /// to it, as on hybrid systems the v2 hierarchy is then mounted without
+    /// CPU controllers.
+    pub fn detect(procfs: &ProcFs) -> Result<CgroupFs> {
+        let mounts = mount::read_mounts(procfs)?;
+
+        let hierarchy = if mounts.v1.iter().any(|m| m.has_controller("cpuacct")) {
+            Hierarchy::V1(mounts.v1)
+        } else if let Some(v2) = mounts.v2 {
+            Hierarchy::V2(v2)
+        } else {
+            return Err(Error::CgroupNotMounted);
+        };
+
+        Ok(CgroupFs { hierarchy })
+    }
+
+    /// Returns the version of the hierarchy.
+    pub fn version(&self) -> CgroupVersion {
+        match self.hierarchy {
+            Hierarchy::V1(_) => CgroupVersion::V1,
+            Hierarchy::V2(_) => CgroupVersion::V2,
+        }
+    }
+
+    /// Returns the cgroup at `path` relative to the root of the hierarchy,
+    /// e.g. `/system.slice/foo.service`.
+    pub fn cgroup(&self, path: impl AsRef<Path>) -> Cgroup {
+        let path = path.as_ref();
+
+        let dirs = match &self.hierarchy {
+            Hierarchy::V1(mounts) => Dirs::V1(
+                mounts
+                    .iter()
+                    .map(|mount| (mount.controllers.clone(), mount.dir(path)))
+                    .collect(),
+            ),
+            Hierarchy::V2(mount) => Dirs::V2(mount.dir(path)),
+        };
+
+        Cgroup { dirs }
+    }
+
+    /// Returns the cgroup of the calling process as listed in
+    /// `self/cgroup` of `procfs`.
+    ///
+    /// If the listed cgroup is not visible below a mount of only part of
+    /// the hierarchy, the root of the mount is used instead. That happens
+    /// in containers without a cgroup namespace. A cgroup missing from a
+    /// mount of the whole hierarchy, e.g. because it was just removed, is
+    /// an `Error::Io` with `NotFound` rather than the root cgroup of the
+    /// whole machine.
+    pub fn current(&self, procfs: &ProcFs) -> Result<Cgroup> {
+        let memberships = mount::read_self_cgroup(procfs)?;
+
+        let dirs = match &self.hierarchy {
+            Hierarchy::V1(mounts) => {
+                let mut dirs = Vec::new();
+                for mount in mounts {
+                    let membership = memberships.iter().find(|membership| {
+                        membership
+                            .controllers
+                            .iter()
+                            .any(|c| mount.has_controller(c))
+                    });
+                    if let Some(membership) = membership {
+                        dirs.push((mount.controllers.clone(), visible_dir(mount, membership)?));
+                    }
+                }
+                Dirs::V1(dirs)
+            }
+            Hierarchy::V2(mount) => {
+                let membership = memberships
+                    .iter()
+                    .find(|membership| membership.controllers.is_empty())
+                    .ok_or(Error::CgroupNotMounted)?;
+                Dirs::V2(visible_dir(mount, membership)?)
+            }
+        };
+
+        Ok(Cgroup { dirs })
+    }
+}
+
+impl Cgroup {
+    /// Returns the cgroup v2 cgroup at the absolute directory `path`.
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Cgroup {
+            dirs: Dirs::V2(path.into()),
+        }
+    }
+
+    /// Returns the directory of this cgroup under the mount of
+    /// `controller`.
+    ///
+    /// With cgroup v2 all controllers share the same directory.
+    pub fn path(&self, controller: &str) -> Option<&Path> {
+        match &self.dirs {
+            Dirs::V1(dirs) => dirs
+                .iter()
+                .find(|(controllers, _)| controllers.iter().any(|c| c == controller))
+                .map(|(_, dir)| dir.as_path()),
+            Dirs::V2(dir) => Some(dir),
+        }
+    }
+
+    /// Reads the CPU accounting of this cgroup.
+    ///
+    /// With cgroup v1 this combines `cpuacct.usage`, `cpuacct.stat` and
+    /// the throttling counters in `cpu.stat`, and with v2 `cpu.stat`.
+    pub fn cpu_stats(&self) -> Result<CgroupCpuStats> {
+        match self.dirs {
+            Dirs::V1(_) => {
+                let cpuacct = self.path("cpuacct").ok_or(Error::CgroupNotMounted)?;
+                let mut stats = CgroupCpuStats {
+                    usage: v1::parse_cpuacct_usage(open(cpuacct.join("cpuacct.usage"))?)?,
+                    ..CgroupCpuStats::default()
+                };
+                v1::parse_cpuacct_stat(open(cpuacct.join("cpuacct.stat"))?, &mut stats)?;
+
+                // The cpu controller may not be mounted at all.
+                if let Some(cpu) = self.path("cpu") {
+                    v1::parse_cpu_stat(open(cpu.join("cpu.stat"))?, &mut stats)?;
+                }
+
+                Ok(stats)
+            }
+            Dirs::V2(ref dir) => v2::parse_cpu_stat(open(dir.join("cpu.stat"))?),
+        }
+    }
+
+    /// Reads `cpuacct.usage_percpu`, indexed by CPU id.
+    ///
+    /// Returns `None` with cgroup v2, which does not account usage per CPU.
+    pub fn cpu_usage_per_cpu(&self) -> Result<Option<Vec<Duration>>> {
+        match self.dirs {
+            Dirs::V1(_) => {
+                let cpuacct = self.path("cpuacct").ok_or(Error::CgroupNotMounted)?;
+                let usage =
+                    v1::parse_cpuacct_usage_percpu(open(cpuacct.join("cpuacct.usage_percpu"))?)?;
+                Ok(Some(usage))
+            }
+            Dirs::V2(_) => Ok(None),
+        }
+    }
+
+    /// Reads the CPU bandwidth limit from `cpu.max` with cgroup v2, or
+    /// `cpu.cfs_quota_us` and `cpu.cfs_period_us` with v1.
+    ///
+    /// Returns `None` if the cgroup is not limited or the cpu controller is
+    /// not enabled for it. Limits of ancestor cgroups are not considered.
+    pub fn cpu_quota(&self) -> Result<Option<CpuQuota>> {
+        let dir = match self.path("cpu") {
+            Some(dir) => dir,
+            None => return Ok(None),
+        };
+
+        let quota = match self.dirs {
+            Dirs::V1(_) => open(dir.join("cpu.cfs_quota_us"))
+                .and_then(|quota| v1::parse_cfs_quota(quota, open(dir.join("cpu.cfs_period_us"))?)),
+            Dirs::V2(_) => open(dir.join("cpu.max")).and_then(v2::parse_cpu_max),
+        };
+
+        not_found_as_none(quota).map(Option::flatten)
+    }
+
+    /// Reads the CPUs the cgroup may run on from `cpuset.cpus.effective`
+    /// with cgroup v2, or `cpuset.effective_cpus` with v1.
+    ///
+    /// Returns `None` if the cpuset controller is not enabled for it.
+    pub fn cpuset_cpus(&self) -> Result<Option<CpuSet>> {
+        let (dir, name) = match self.dirs {
+            Dirs::V1(_) => match self.path("cpuset") {
+                Some(dir) => (dir, "cpuset.effective_cpus"),
+                None => return Ok(None),
+            },
+            Dirs::V2(ref dir) => (dir.as_path(), "cpuset.cpus.effective"),
+        };
+
+        let cpus = std::fs::read_to_string(dir.join(name))
+            .map_err(Error::from)
+            .and_then(|cpus| cpus.parse());
+
+        not_found_as_none(cpus)
+    }
+}
+
+impl CgroupCpuDelta {
+    /// Computes `later - earlier`.
+    ///
+    /// Returns `Error::CounterRegression` if any counter in `later` is
+    /// smaller than in `earlier`.
+    pub fn between(earlier: &CgroupCpuStats, later: &CgroupCpuStats) -> Result<CgroupCpuDelta> {
+        Ok(CgroupCpuDelta {
+            usage: sub("usage", later.usage, earlier.usage)?,
+            user: sub("user", later.user, earlier.user)?,
+            system: sub("system", later.system, earlier.system)?,
+            nr_periods: sub_opt("nr_periods", later.nr_periods, earlier.nr_periods)?,
+            nr_throttled: sub_opt("nr_throttled", later.nr_throttled, earlier.nr_throttled)?,
+            throttled: sub_opt("throttled", later.throttled, earlier.throttled)?,
+            nr_bursts: sub_opt("nr_bursts", later.nr_bursts, earlier.nr_bursts)?,
+            burst: sub_opt("burst", later.burst, earlier.burst)?,
+        })
+    }
+
+    /// Average number of CPUs kept busy over `elapsed` wall time.
+    pub fn cpus(&self, elapsed: Duration) -> f64 {
+        if elapsed.is_zero() {
+            0.0
+        } else {
+            self.usage.as_secs_f64() / elapsed.as_secs_f64()
+        }
+    }
+
+    /// Fraction of enforcement periods in which the cgroup was throttled.
+    pub fn throttled_fraction(&self) -> Option<f64> {
+        let periods = self.nr_periods?;
+        let throttled = self.nr_throttled?;
+
+        if periods == 0 {
+            Some(0.0)
+        } else {
+            Some(throttled as f64 / periods as f64)
+        }
+    }
+}
+
+impl Sub for CgroupCpuStats {
+    type Output = Result<CgroupCpuDelta>;
+
+    fn sub(self, earlier: CgroupCpuStats) -> Result<CgroupCpuDelta> {
+        CgroupCpuDelta::between(&earlier, &self)
+    }
+}
+
+fn visible_dir(mount: &Mount, membership: &Membership) -> Result<PathBuf> {
+    let dir = mount.dir(&membership.path);
+    if dir.is_dir() {
+        Ok(dir)
+    } else if mount.root != Path::new("/") {
+        Ok(mount.mount_point.clone())
+    } else {
+        let msg = format!("cgroup {} not found", dir.display());
+        Err(io::Error::new(io::ErrorKind::NotFound, msg).into())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use std::path::PathBuf;
+
+    use super::mount::{Membership, Mount};
+    use super::{visible_dir, CgroupFs, CgroupVersion};
+    use crate::{Error, ProcFs};
+
+    const FIXTURES: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures");
+
+    #[test]
+    fn test_fixture_current() {
+        let procfs = ProcFs::new(format!("{}/proc", FIXTURES));
+        let cgroupfs = CgroupFs::new(format!("{}/cgroup2", FIXTURES));
+
+        let cgroup = cgroupfs.current(&procfs).unwrap();
+        assert!(cgroup.path("cpu").unwrap().ends_with("kubepods/pod1"));
+        assert_eq!(cgroup.cpu_usage_per_cpu().unwrap(), None);
+
+        let earlier = cgroup.cpu_stats().unwrap();
+        assert_eq!(earlier.usage, Duration::from_micros(8261417));
+        assert_eq!(earlier.nr_throttled, Some(41));
+
+        let mut later = earlier;
+        later.usage += Duration::from_millis(500);
+        later.nr_periods = later.nr_periods.map(|n| n + 10);
+        later.nr_throttled = later.nr_throttled.map(|n| n + 5);
+
+        let delta = (later - earlier).unwrap();
+        assert_eq!(delta.cpus(Duration::from_secs(1)), 0.5);
+        assert_eq!(delta.throttled_fraction(), Some(0.5));
+    }
+
+    #[test]
+    fn test_visible_dir() {
+        let membership = Membership {
+            controllers: Vec::new(),
+            path: PathBuf::from("/kubepods/gone"),
+        };
+        let mut mount = Mount {
+            controllers: Vec::new(),
+            root: PathBuf::from("/"),
+            mount_point: PathBuf::from(format!("{}/cgroup2", FIXTURES)),
+        };
+
+        // the whole hierarchy is mounted, so the cgroup is really missing
+        match visible_dir(&mount, &membership) {
+            Err(Error::Io(err)) => assert_eq!(err.kind(), std::io::ErrorKind::NotFound),
+            other => panic!("unexpected {:?}", other),
+        }
+
+        // only a container's own cgroup is mounted
+        mount.root = PathBuf::from("/docker/abc");
+        assert_eq!(visible_dir(&mount, &membership).unwrap(), mount.mount_point);
+    }
+
+    #[test]
+    fn test_fixture_v1() {
+        let cgroupfs = CgroupFs::new_v1(format!("{}/cgroup1", FIXTURES));
+        assert_eq!(cgroupfs.version(), CgroupVersion::V1);
+
+        let cgroup = cgroupfs.cgroup("/docker/abc");
+        let stats = cgroup.cpu_stats().unwrap();
+        assert_eq!(stats.usage, Duration::from_micros(8261417));
+        assert!(!stats.user.is_zero());
+        assert_eq!(stats.nr_throttled, Some(41));
+        assert_eq!(stats.throttled, Some(Duration::from_micros(1893021)));
+
+        let usage = cgroup.cpu_usage_per_cpu().unwrap().unwrap();
+        assert_eq!(usage.len(), 2);
+    }
+}
@@ -0,0 +1,223 @@
+use std::io::BufRead;
+use std::path::{Path, PathBuf};
+
+use crate::{Error, ProcFs, Result};
+
+/// A cgroup hierarchy mount found in mountinfo.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct Mount {
+    /// controllers attached to a v1 hierarchy, empty for v2
+    pub(crate) controllers: Vec<String>,
+    /// the cgroup that is mounted, relative to the root of the hierarchy
+    pub(crate) root: PathBuf,
+    /// where it is mounted
+    pub(crate) mount_point: PathBuf,
+}
+
+/// Cgroup mounts of the calling process.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub(crate) struct Mounts {
+    pub(crate) v1: Vec<Mount>,
+    pub(crate) v2: Option<Mount>,
+}
+
+/// One line of /proc/self/cgroup.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct Membership {
+    pub(crate) controllers: Vec<String>,
+    pub(crate) path: PathBuf,
+}
+
+impl Mount {
+    pub(crate) fn has_controller(&self, controller: &str) -> bool {
+        self.controllers.iter().any(|c| c == controller)
+    }
+
+    /// Returns the directory of the cgroup `path` under this mount.
+    ///
+    /// Without a cgroup namespace, a container sees its host side path
+    /// while only its own cgroup is mounted, so the mounted root is
+    /// stripped first.
+    pub(crate) fn dir(&self, path: &Path) -> PathBuf {
+        let path = path.strip_prefix(&self.root).unwrap_or(path);
+        let path = path.strip_prefix("/").unwrap_or(path);
+        self.mount_point.join(path)
+    }
+}
+
+// 36 35 98:0 /mnt1 /mnt2 rw,noatime master:1 - cgroup cgroup rw,cpu,cpuacct
+//
+// The number of optional fields before "-" varies, and the super options of
+// a v1 mount list its controllers.
+pub(crate) fn parse_mountinfo<R: BufRead>(fd: R) -> Result<Mounts> {
+    let mut mounts = Mounts::default();
+
+    for (i, line) in fd.lines().enumerate() {
+        let line = line?;
+        let malformed = || Error::Malformed {
+            line: i + 1,
+            column: 1,
+            token: line.clone(),
+        };
+
+        let mut fields = line.split_ascii_whitespace();
+        let root = fields.nth(3).ok_or_else(malformed)?;
+        let mount_point = fields.next().ok_or_else(malformed)?;
+
+        let mut fields = fields.skip_while(|field| *field != "-").skip(1);
+        let fstype = fields.next().ok_or_else(malformed)?;
+        let super_options = fields.nth(1).unwrap_or_default();
+
+        let mut mount = Mount {
+            controllers: Vec::new(),
+            root: PathBuf::from(unescape(root)),
+            mount_point: PathBuf::from(unescape(mount_point)),
+        };
+
+        match fstype {
+            "cgroup2" if mounts.v2.is_none() => mounts.v2 = Some(mount),
+            "cgroup" => {
+                mount.controllers = super_options
+                    .split(',')
+                    .filter(|option| !is_mount_flag(option))
+                    .map(String::from)
+                    .collect();
+                mounts.v1.push(mount);
+            }
+            _ => (),
+        }
+    }
+
+    Ok(mounts)
+}
+
+// Super options of a v1 mount that are not controllers, see
+// cgroup1_parse_param() in kernel/cgroup/cgroup-v1.c. Options with values
+// such as name= and release_agent= are not controllers either.
+fn is_mount_flag(option: &str) -> bool {
+    const FLAGS: &[&str] = &[
+        "rw",
+        "ro",
+        "none",
+        "all",
+        "noprefix",
+        "clone_children",
+        "cpuset_v2_mode",
+        "xattr",
+        "favordynmods",
+        "nofavordynmods",
+    ];
+
+    FLAGS.contains(&option) || option.contains('=')
+}
+
+// Each line is "hierarchy-ID:controller-list:path". The unified hierarchy
+// has ID 0 and an empty controller list.
+pub(crate) fn parse_self_cgroup<R: BufRead>(fd: R) -> Result<Vec<Membership>> {
+    let mut memberships = Vec::new();
+
+    for (i, line) in fd.lines().enumerate() {
+        let line = line?;
+
+        let mut fields = line.splitn(3, ':');
+        let (controllers, path) = match (fields.next(), fields.next(), fields.next()) {
+            (Some(_id), Some(controllers), Some(path)) => (controllers, path),
+            _ => {
+                return Err(Error::Malformed {
+                    line: i + 1,
+                    column: 1,
+                    token: line,
+                })
+            }
+        };
+
+        memberships.push(Membership {
+            controllers: controllers
+                .split(',')
+                .filter(|c| !c.is_empty())
+                .map(String::from)
+                .collect(),
+            path: PathBuf::from(path),
+        });
+    }
+
+    Ok(memberships)
+}
+
+pub(crate) fn read_mounts(procfs: &ProcFs) -> Result<Mounts> {
+    parse_mountinfo(procfs.open("self/mountinfo")?)
+}
+
+pub(crate) fn read_self_cgroup(procfs: &ProcFs) -> Result<Vec<Membership>> {
+    parse_self_cgroup(procfs.open("self/cgroup")?)
+}
+
+// mountinfo escapes space, tab, newline and backslash as octal.
+fn unescape(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    let mut rest = s;
+
+    while let Some(i) = rest.find('\\') {
+        out.push_str(&rest[..i]);
+        let code = rest
+            .get(i + 1..i + 4)
+            .and_then(|c| u8::from_str_radix(c, 8).ok());
+        match code {
+            Some(c) => {
+                out.push(c as char);
+                rest = &rest[i + 4..];
+            }
+            None => {
+                out.push('\\');
+                rest = &rest[i + 1..];
+            }
+        }
+    }
+
+    out.push_str(rest);
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::{Path, PathBuf};
+
+    use super::{parse_mountinfo, parse_self_cgroup};
+
+    #[test]
+    fn test_parse_mountinfo() {
+        let input = "32 24 0:28 / /sys/fs/cgroup rw,relatime - tmpfs tmpfs rw,mode=755\n\
+                     33 32 0:29 /docker/abc /sys/fs/cgroup/cpu,cpuacct rw shared:9 - cgroup cgroup rw,cpu,cpuacct\n\
+                     41 32 0:37 / /sys/fs/cgroup/systemd rw - cgroup cgroup rw,xattr,name=systemd\n\
+                     43 32 0:39 / /sys/fs/cgroup/cpuset rw - cgroup cgroup rw,cpuset,noprefix,clone_children\n\
+                     42 32 0:38 / /sys/fs/cgroup/my\\040unified rw - cgroup2 cgroup2 rw\n";
+        let mounts = parse_mountinfo(input.as_bytes()).unwrap();
+
+        assert_eq!(mounts.v1.len(), 3);
+        assert_eq!(mounts.v1[0].controllers, vec!["cpu", "cpuacct"]);
+        assert!(mounts.v1[1].controllers.is_empty());
+        assert_eq!(mounts.v1[2].controllers, vec!["cpuset"]);
+        assert_eq!(
+            mounts.v1[0].dir(Path::new("/docker/abc")),
+            PathBuf::from("/sys/fs/cgroup/cpu,cpuacct")
+        );
+
+        let v2 = mounts.v2.unwrap();
+        assert_eq!(v2.mount_point, PathBuf::from("/sys/fs/cgroup/my unified"));
+        assert_eq!(
+            v2.dir(Path::new("/user.slice")),
+            PathBuf::from("/sys/fs/cgroup/my unified/user.slice")
+        );
+    }
+
+    #[test]
+    fn test_parse_self_cgroup() {
+        let input = "4:cpu,cpuacct:/docker/abc\n1:name=systemd:/\n0::/init.scope\n";
+        let memberships = parse_self_cgroup(input.as_bytes()).unwrap();
+
+        assert_eq!(memberships.len(), 3);
+        assert_eq!(memberships[0].controllers, vec!["cpu", "cpuacct"]);
+        assert!(memberships[2].controllers.is_empty());
+        assert_eq!(memberships[2].path, PathBuf::from("/init.scope"));
+    }
+}
@@ -0,0 +1,140 @@
+use std::io::BufRead;
+use std::time::Duration;
+
+use super::{CgroupCpuStats, CpuQuota};
+use crate::linux::parse::Fields;
+use crate::{Error, Result};
+
+// cpuacct.usage holds the total CPU time in nanoseconds.
+pub(crate) fn parse_cpuacct_usage<R: BufRead>(fd: R) -> Result<Duration> {
+    let usage = parse_cpuacct_usage_percpu(fd)?;
+    match usage.as_slice() {
+        [usage] => Ok(*usage),
+        _ => Err(Error::MissingField {
+            line: 1,
+            column: 1,
+            field: "usage",
+        }),
+    }
+}
+
+// cpuacct.usage_percpu has one nanosecond count per possible CPU.
+pub(crate) fn parse_cpuacct_usage_percpu<R: BufRead>(mut fd: R) -> Result<Vec<Duration>> {
+    let mut line = String::new();
+    let _len = fd.read_line(&mut line)?;
+
+    let mut fields = Fields::new(1, &line);
+    let mut usage = Vec::new();
+    while let Some(nanos) = fields.parse()? {
+        usage.push(Duration::from_nanos(nanos));
+    }
+
+    Ok(usage)
+}
+
+// cpuacct.stat holds "user" and "system" in USER_HZ clock ticks.
+pub(crate) fn parse_cpuacct_stat<R: BufRead>(fd: R, stats: &mut CgroupCpuStats) -> Result<()> {
+    for (i, line) in fd.lines().enumerate() {
+        let line = line?;
+        let mut fields = Fields::new(i + 1, &line);
+
+        match fields.next() {
+            Some("user") => stats.user = fields.require_ticks("user")?,
+            Some("system") => stats.system = fields.require_ticks("system")?,
+            _ => (),
+        }
+    }
+
+    Ok(())
+}
+
+// The v1 cpu.stat counts throttled and burst time in nanoseconds.
+pub(crate) fn parse_cpu_stat<R: BufRead>(fd: R, stats: &mut CgroupCpuStats) -> Result<()> {
+    for (i, line) in fd.lines().enumerate() {
+        let line = line?;
+        let mut fields = Fields::new(i + 1, &line);
+
+        match fields.next() {
+            Some("nr_periods") => stats.nr_periods = Some(fields.require("value")?),
+            Some("nr_throttled") => stats.nr_throttled = Some(fields.require("value")?),
+            Some("throttled_time") => {
+                stats.throttled = Some(Duration::from_nanos(fields.require("value")?))
+            }
+            Some("nr_bursts") => stats.nr_bursts = Some(fields.require("value")?),
+            Some("burst_time") => {
+                stats.burst = Some(Duration::from_nanos(fields.require("value")?))
+            }
+            _ => (),
+        }
+    }
+
+    Ok(())
+}
+
+// cpu.cfs_quota_us is -1 when unlimited.
+pub(crate) fn parse_cfs_quota<R: BufRead, P: BufRead>(
+    mut quota_fd: R,
+    mut period_fd: P,
+) -> Result<Option<CpuQuota>> {
+    let mut line = String::new();
+    let _len = quota_fd.read_line(&mut line)?;
+    let quota: i64 = Fields::new(1, &line).require("cfs_quota_us")?;
+    if quota < 0 {
+        return Ok(None);
+    }
+
+    line.clear();
+    let _len = period_fd.read_line(&mut line)?;
+    let period = Fields::new(1, &line).require("cfs_period_us")?;
+
+    Ok(Some(CpuQuota {
+        quota: Duration::from_micros(quota as u64),
+        period: Duration::from_micros(period),
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::{parse_cfs_quota, parse_cpu_stat, parse_cpuacct_usage, parse_cpuacct_usage_percpu};
+    use crate::CgroupCpuStats;
+
+    #[test]
+    fn test_parse_cpuacct_usage() {
+        let usage = parse_cpuacct_usage("1500000000\n".as_bytes()).unwrap();
+        assert_eq!(usage, Duration::from_millis(1500));
+
+        let usage = parse_cpuacct_usage_percpu("1000 2000 0 \n".as_bytes()).unwrap();
+        assert_eq!(
+            usage,
+            vec![
+                Duration::from_nanos(1000),
+                Duration::from_nanos(2000),
+                Duration::ZERO
+            ]
+        );
+    }
+
+    #[test]
+    fn test_parse_cpu_stat() {
+        let mut stats = CgroupCpuStats::default();
+        let input = "nr_periods 10\nnr_throttled 2\nthrottled_time 5000000\n";
+        parse_cpu_stat(input.as_bytes(), &mut stats).unwrap();
+        assert_eq!(stats.nr_periods, Some(10));
+        assert_eq!(stats.nr_throttled, Some(2));
+        assert_eq!(stats.throttled, Some(Duration::from_millis(5)));
+        assert_eq!(stats.nr_bursts, None);
+    }
+
+    #[test]
+    fn test_parse_cfs_quota() {
+        let quota = parse_cfs_quota("-1\n".as_bytes(), "100000\n".as_bytes()).unwrap();
+        assert_eq!(quota, None);
+
+        let quota = parse_cfs_quota("50000\n".as_bytes(), "100000\n".as_bytes())
+            .unwrap()
+            .unwrap();
+        assert_eq!(quota.cpus(), 0.5);
+    }
+}
@@ -0,0 +1,114 @@
+use std::io::BufRead;
+use std::time::Duration;
+
+use super::{CgroupCpuStats, CpuQuota};
+use crate::linux::parse::Fields;
+use crate::{Error, Result};
+
+// cpu.stat has one "key value" pair per line. The throttling counters are
+// only present when the cpu controller is enabled for the cgroup, and the
+// burst counters were added in Linux 5.14.
+pub(crate) fn parse_cpu_stat<R: BufRead>(fd: R) -> Result<CgroupCpuStats> {
+    let mut usage = None;
+    let mut user = None;
+    let mut system = None;
+    let mut stats = CgroupCpuStats::default();
+
+    for (i, line) in fd.lines().enumerate() {
+        let line = line?;
+        let mut fields = Fields::new(i + 1, &line);
+
+        let key = match fields.next() {
+            Some(key) => key,
+            None => continue,
+        };
+
+        match key {
+            "usage_usec" => usage = Some(micros(fields.require("value")?)),
+            "user_usec" => user = Some(micros(fields.require("value")?)),
+            "system_usec" => system = Some(micros(fields.require("value")?)),
+            "nr_periods" => stats.nr_periods = Some(fields.require("value")?),
+            "nr_throttled" => stats.nr_throttled = Some(fields.require("value")?),
+            "throttled_usec" => stats.throttled = Some(micros(fields.require("value")?)),
+            "nr_bursts" => stats.nr_bursts = Some(fields.require("value")?),
+            "burst_usec" => stats.burst = Some(micros(fields.require("value")?)),
+            _ => (),
+        }
+    }
+
+    let missing = |field| Error::MissingField {
+        line: 1,
+        column: 1,
+        field,
+    };
+
+    stats.usage = usage.ok_or_else(|| missing("usage_usec"))?;
+    stats.user = user.ok_or_else(|| missing("user_usec"))?;
+    stats.system = system.ok_or_else(|| missing("system_usec"))?;
+
+    Ok(stats)
+}
+
+// cpu.max is "$MAX $PERIOD" in microseconds, where $MAX may be "max".
+pub(crate) fn parse_cpu_max<R: BufRead>(mut fd: R) -> Result<Option<CpuQuota>> {
+    let mut line = String::new();
+    let _len = fd.read_line(&mut line)?;
+
+    let mut fields = Fields::new(1, &line);
+    let quota = match fields.next() {
+        Some("max") => return Ok(None),
+        Some(token) => token.parse().map_err(|_| fields.malformed(token))?,
+        None => return Err(fields.missing("max")),
+    };
+    let period = fields.parse()?.unwrap_or(100_000);
+
+    Ok(Some(CpuQuota {
+        quota: micros(quota),
+        period: micros(period),
+    }))
+}
+
+fn micros(usec: u64) -> Duration {
+    Duration::from_micros(usec)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::{parse_cpu_max, parse_cpu_stat};
+    use crate::Error;
+
+    #[test]
+    fn test_parse_cpu_stat() {
+        let input = "usage_usec 3000\nuser_usec 2000\nsystem_usec 1000\n";
+        let stats = parse_cpu_stat(input.as_bytes()).unwrap();
+        assert_eq!(stats.usage, Duration::from_millis(3));
+        assert_eq!(stats.user, Duration::from_millis(2));
+        assert_eq!(stats.system, Duration::from_millis(1));
+        assert_eq!(stats.nr_periods, None);
+        assert_eq!(stats.burst, None);
+    }
+
+    #[test]
+    fn test_parse_cpu_stat_missing_usage() {
+        match parse_cpu_stat("user_usec 2000\n".as_bytes()) {
+            Err(Error::MissingField {
+                field: "usage_usec",
+                ..
+            }) => (),
+            other => panic!("unexpected {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_parse_cpu_max() {
+        assert_eq!(parse_cpu_max("max 100000\n".as_bytes()).unwrap(), None);
+
+        let quota = parse_cpu_max("150000 100000\n".as_bytes())
+            .unwrap()
+            .unwrap();
+        assert_eq!(quota.quota, Duration::from_millis(150));
+        assert_eq!(quota.cpus(), 1.5);
+    }
+}
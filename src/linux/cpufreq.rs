@@ -0,0 +1,110 @@
+use std::io::BufRead;
+use std::str::FromStr;
+use std::time::Duration;
+
+use super::parse::Fields;
+use crate::{Error, Result};
+
+/// Frequency scaling state of one CPU from its cpufreq sysfs directory.
+///
+/// Frequencies are in kHz, as the kernel reports them.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct CpuFreq {
+    /// current frequency as last seen by the cpufreq driver
+    pub cur: u64,
+    /// lowest frequency the governor may pick
+    pub min: u64,
+    /// highest frequency the governor may pick
+    pub max: u64,
+    /// highest frequency the hardware supports
+    pub cpuinfo_max: u64,
+    /// name of the scaling governor, e.g. "schedutil"
+    pub governor: String,
+    /// whether turbo or boost frequencies are enabled, if the driver says
+    pub boost: Option<bool>,
+    /// time spent at each frequency, if the kernel keeps cpufreq statistics
+    pub time_in_state: Option<TimeInState>,
+    /// number of frequency transitions, if the kernel keeps cpufreq
+    /// statistics
+    pub total_trans: Option<u64>,
+}
+
+impl CpuFreq {
+    /// Current frequency as a fraction of the highest hardware frequency.
+    pub fn cur_fraction(&self) -> f64 {
+        if self.cpuinfo_max == 0 {
+            return 0.0;
+        }
+        self.cur as f64 / self.cpuinfo_max as f64
+    }
+}
+
+/// Time spent at each frequency, from `stats/time_in_state`.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct TimeInState {
+    /// frequency in kHz and the time spent running at it
+    pub states: Vec<(u64, Duration)>,
+}
+
+impl TimeInState {
+    /// Parses the contents of `stats/time_in_state` from `fd`.
+    pub fn from_reader<R: BufRead>(fd: R) -> Result<TimeInState> {
+        let mut states = Vec::new();
+
+        // "<frequency> <time>" per line, time in USER_HZ
+        for (n, line) in fd.lines().enumerate() {
+            let line = line?;
+            let mut fields = Fields::new(n + 1, &line);
+            let freq = match fields.parse()? {
+                Some(freq) => freq,
+                None => continue,
+            };
+            let time = fields.require_ticks("time")?;
+            states.push((freq, time));
+        }
+
+        Ok(TimeInState { states })
+    }
+
+    /// Average frequency in kHz weighted by the time spent at each one.
+    pub fn average(&self) -> Option<u64> {
+        let total: u128 = self.states.iter().map(|(_, time)| time.as_nanos()).sum();
+        if total == 0 {
+            return None;
+        }
+
+        let weighted: u128 = self
+            .states
+            .iter()
+            .map(|&(freq, time)| u128::from(freq) * time.as_nanos())
+            .sum();
+        Some((weighted / total) as u64)
+    }
+}
+
+impl FromStr for TimeInState {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<TimeInState> {
+        TimeInState::from_reader(s.as_bytes())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::TimeInState;
+    use crate::test_util::ticks;
+
+    #[test]
+    fn test_time_in_state() {
+        let stats: TimeInState = "800000 300\n1600000 100\n\n".parse().unwrap();
+        assert_eq!(
+            stats.states,
+            vec![(800000, ticks(300)), (1600000, ticks(100))]
+        );
+        assert_eq!(stats.average(), Some(1000000));
+
+        assert!("800000\n".parse::<TimeInState>().is_err());
+        assert_eq!(TimeInState::default().average(), None);
+    }
+}
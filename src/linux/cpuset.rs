@@ -0,0 +1,251 @@
+use std::collections::btree_set::{self, BTreeSet};
+use std::collections::BTreeMap;
+use std::fmt;
+use std::iter::{Copied, FromIterator};
+use std::mem;
+use std::ops::Add;
+use std::str::FromStr;
+
+use crate::{Error, Result};
+
+/// A set of kernel CPU ids.
+///
+/// Parses from and formats to the kernel's cpulist format, e.g.
+/// `0-3,8-11,16`, as used by sysfs and cgroup cpusets.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash)]
+pub struct CpuSet {
+    cpus: BTreeSet<usize>,
+}
+
+impl CpuSet {
+    /// CPU ids must be below this, the largest `CONFIG_NR_CPUS` the kernel
+    /// can be built with. Parsing rejects larger ids so that a bogus range
+    /// cannot exhaust memory.
+    pub const LIMIT: usize = 8192;
+
+    pub fn new() -> Self {
+        CpuSet::default()
+    }
+
+    pub fn len(&self) -> usize {
+        self.cpus.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.cpus.is_empty()
+    }
+
+    pub fn contains(&self, cpu: usize) -> bool {
+        self.cpus.contains(&cpu)
+    }
+
+    /// Adds `cpu`, returning whether it was not in the set yet.
+    pub fn insert(&mut self, cpu: usize) -> bool {
+        self.cpus.insert(cpu)
+    }
+
+    /// Removes `cpu`, returning whether it was in the set.
+    pub fn remove(&mut self, cpu: usize) -> bool {
+        self.cpus.remove(&cpu)
+    }
+
+    /// Lowest CPU id in the set.
+    pub fn first(&self) -> Option<usize> {
+        self.cpus.first().copied()
+    }
+
+    /// Iterates over the CPU ids in ascending order.
+    pub fn iter(&self) -> Copied<btree_set::Iter<'_, usize>> {
+        self.cpus.iter().copied()
+    }
+
+    pub fn union(&self, other: &CpuSet) -> CpuSet {
+        self.cpus.union(&other.cpus).copied().collect()
+    }
+
+    pub fn intersection(&self, other: &CpuSet) -> CpuSet {
+        self.cpus.intersection(&other.cpus).copied().collect()
+    }
+
+    /// CPUs in `self` but not in `other`.
+    pub fn difference(&self, other: &CpuSet) -> CpuSet {
+        self.cpus.difference(&other.cpus).copied().collect()
+    }
+
+    pub fn is_subset(&self, other: &CpuSet) -> bool {
+        self.cpus.is_subset(&other.cpus)
+    }
+
+    /// Keeps only the entries of per-CPU stats or deltas for CPUs in the
+    /// set.
+    pub fn filter<T: Clone>(&self, per_cpu: &BTreeMap<usize, T>) -> BTreeMap<usize, T> {
+        per_cpu
+            .iter()
+            .filter(|(cpu, _)| self.contains(**cpu))
+            .map(|(&cpu, value)| (cpu, value.clone()))
+            .collect()
+    }
+
+    /// Sums per-CPU stats or deltas of the CPUs in the set.
+    ///
+    /// Returns `None` if none of them are in `per_cpu`.
+    pub fn sum<T>(&self, per_cpu: &BTreeMap<usize, T>) -> Option<T>
+    where
+        T: Copy + Add<Output = T>,
+    {
+        self.iter()
+            .filter_map(|cpu| per_cpu.get(&cpu).copied())
+            .reduce(|sum, value| sum + value)
+    }
+
+    /// Converts to the mask taken by sched_setaffinity(2).
+    ///
+    /// Returns `None` if the set has CPUs beyond `libc::CPU_SETSIZE`.
+    pub fn to_cpu_set_t(&self) -> Option<libc::cpu_set_t> {
+        let mut set: libc::cpu_set_t = unsafe { mem::zeroed() };
+        for cpu in self.iter() {
+            if cpu >= libc::CPU_SETSIZE as usize {
+                return None;
+            }
+            unsafe { libc::CPU_SET(cpu, &mut set) };
+        }
+        Some(set)
+    }
+}
+
+impl From<&libc::cpu_set_t> for CpuSet {
+    fn from(set: &libc::cpu_set_t) -> CpuSet {
+        (0..libc::CPU_SETSIZE as usize)
+            .filter(|&cpu| unsafe { libc::CPU_ISSET(cpu, set) })
+            .collect()
+    }
+}
+
+impl FromStr for CpuSet {
+    type Err = Error;
+
+    /// Parses a kernel cpulist such as `0-3,8-11,16`.
+    ///
+    /// Ids of `CpuSet::LIMIT` or above are rejected as malformed.
+    fn from_str(s: &str) -> Result<CpuSet> {
+        let malformed = || Error::Malformed {
+            line: 1,
+            column: 1,
+            token: s.to_owned(),
+        };
+
+        let mut cpus = CpuSet::new();
+        for range in s.trim().split(',').filter(|range| !range.is_empty()) {
+            let (first, last) = match range.split_once('-') {
+                Some((first, last)) => (first, last),
+                None => (range, range),
+            };
+            let first: usize = first.parse().map_err(|_| malformed())?;
+            let last: usize = last.parse().map_err(|_| malformed())?;
+            if first > last || last >= CpuSet::LIMIT {
+                return Err(malformed());
+            }
+            cpus.extend(first..=last);
+        }
+
+        Ok(cpus)
+    }
+}
+
+impl fmt::Display for CpuSet {
+    /// Formats as a kernel cpulist, collapsing consecutive ids to ranges.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut cpus = self.iter().peekable();
+        let mut separator = "";
+
+        while let Some(first) = cpus.next() {
+            let mut last = first;
+            while cpus.peek() == Some(&(last + 1)) {
+                last = cpus.next().unwrap_or(last);
+            }
+
+            if first == last {
+                write!(f, "{}{}", separator, first)?;
+            } else {
+                write!(f, "{}{}-{}", separator, first, last)?;
+            }
+            separator = ",";
+        }
+
+        Ok(())
+    }
+}
+
+impl FromIterator<usize> for CpuSet {
+    fn from_iter<I: IntoIterator<Item = usize>>(iter: I) -> CpuSet {
+        CpuSet {
+            cpus: iter.into_iter().collect(),
+        }
+    }
+}
+
+impl Extend<usize> for CpuSet {
+    fn extend<I: IntoIterator<Item = usize>>(&mut self, iter: I) {
+        self.cpus.extend(iter)
+    }
+}
+
+impl<'a> IntoIterator for &'a CpuSet {
+    type Item = usize;
+    type IntoIter = Copied<btree_set::Iter<'a, usize>>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use super::CpuSet;
+
+    #[test]
+    fn test_parse() {
+        let cpus: CpuSet = "0-2,8,4-5\n".parse().unwrap();
+        assert_eq!(cpus.iter().collect::<Vec<_>>(), vec![0, 1, 2, 4, 5, 8]);
+        assert!("\n".parse::<CpuSet>().unwrap().is_empty());
+        assert!("3-1".parse::<CpuSet>().is_err());
+        assert!("a".parse::<CpuSet>().is_err());
+        assert!("0-18446744073709551615".parse::<CpuSet>().is_err());
+        assert!("8192".parse::<CpuSet>().is_err());
+        assert_eq!("8191".parse::<CpuSet>().unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_format() {
+        let cpus: CpuSet = [16, 0, 1, 2, 3, 8, 9, 10, 11, 13].into_iter().collect();
+        assert_eq!(cpus.to_string(), "0-3,8-11,13,16");
+        assert_eq!(CpuSet::new().to_string(), "");
+    }
+
+    #[test]
+    fn test_set_operations() {
+        let a: CpuSet = "0-3".parse().unwrap();
+        let b: CpuSet = "2-5".parse().unwrap();
+        assert_eq!(a.union(&b).to_string(), "0-5");
+        assert_eq!(a.intersection(&b).to_string(), "2-3");
+        assert_eq!(a.difference(&b).to_string(), "0-1");
+        assert!(a.intersection(&b).is_subset(&a));
+
+        let per_cpu = BTreeMap::from([(1, 10), (2, 20), (7, 70)]);
+        assert_eq!(a.filter(&per_cpu), BTreeMap::from([(1, 10), (2, 20)]));
+        assert_eq!(a.sum(&per_cpu), Some(30));
+        assert_eq!(b.difference(&a).sum(&per_cpu), None);
+    }
+
+    #[test]
+    fn test_cpu_set_t() {
+        let cpus: CpuSet = "0,5-6,1023".parse().unwrap();
+        let set = cpus.to_cpu_set_t().unwrap();
+        assert_eq!(CpuSet::from(&set), cpus);
+
+        let too_large: CpuSet = [libc::CPU_SETSIZE as usize].into_iter().collect();
+        assert!(too_large.to_cpu_set_t().is_none());
+    }
+}
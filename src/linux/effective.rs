@@ -0,0 +1,142 @@
+use super::affinity::{online_cpus, sched_getaffinity};
+use crate::{CgroupFs, CpuUtilization, ProcFs, Result};
+
+/// What limits the CPU capacity available to the calling thread.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum CpuLimit {
+    /// nothing, all online CPUs can be used
+    Online,
+    /// the cgroup's cpuset
+    Cpuset,
+    /// the thread's scheduler affinity mask
+    Affinity,
+    /// the cgroup's CPU bandwidth quota
+    Quota,
+}
+
+/// CPU capacity actually available to the calling thread.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct EffectiveCpus {
+    /// number of CPUs worth of time that can be used, possibly fractional
+    pub cpus: f64,
+    /// the tightest of the limits below
+    pub limit: CpuLimit,
+    /// CPUs online in the system
+    pub online: usize,
+    /// CPUs in the cgroup's cpuset, if there is one
+    pub cpuset: Option<usize>,
+    /// CPUs in the affinity mask
+    pub affinity: usize,
+    /// CPUs worth of time allowed by the cgroup's quota, if there is one
+    pub quota: Option<f64>,
+}
+
+impl EffectiveCpus {
+    /// Combines the cgroup `cpu.max` quota, the cgroup cpuset and the
+    /// calling thread's affinity mask.
+    ///
+    /// Missing cgroup mounts or controllers are treated as no limit.
+    pub fn detect(procfs: &ProcFs) -> Result<EffectiveCpus> {
+        let cgroup = match CgroupFs::detect(procfs) {
+            Ok(cgroupfs) => Some(cgroupfs.current(procfs)?),
+            Err(crate::Error::CgroupNotMounted) => None,
+            Err(err) => return Err(err),
+        };
+
+        let (cpuset, quota) = match cgroup {
+            Some(cgroup) => (
+                cgroup.cpuset_cpus()?.map(|cpus| cpus.len()),
+                cgroup.cpu_quota()?.map(|quota| quota.cpus()),
+            ),
+            None => (None, None),
+        };
+
+        let affinity = sched_getaffinity()?.len();
+
+        Ok(EffectiveCpus::from_limits(
+            online_cpus()?,
+            cpuset,
+            affinity,
+            quota,
+        ))
+    }
+
+    /// Picks the tightest of the given limits.
+    pub fn from_limits(
+        online: usize,
+        cpuset: Option<usize>,
+        affinity: usize,
+        quota: Option<f64>,
+    ) -> EffectiveCpus {
+        let mut cpus = online as f64;
+        let mut limit = CpuLimit::Online;
+
+        // On ties the earlier limit wins, as the affinity mask usually
+        // just mirrors the cpuset.
+        let limits = [
+            (cpuset.map(|n| n as f64), CpuLimit::Cpuset),
+            (Some(affinity as f64), CpuLimit::Affinity),
+            (quota, CpuLimit::Quota),
+        ];
+        for (value, reason) in limits {
+            if let Some(value) = value.filter(|&value| value < cpus) {
+                cpus = value;
+                limit = reason;
+            }
+        }
+
+        EffectiveCpus {
+            cpus,
+            limit,
+            online,
+            cpuset,
+            affinity,
+            quota,
+        }
+    }
+
+    /// Rescales system wide busy time to the share of the capacity
+    /// available here.
+    ///
+    /// A fully busy 2 CPU quota on an 8 CPU host is 25% busy system wide
+    /// and 100% busy here.
+    pub fn normalize(&self, utilization: &CpuUtilization) -> f64 {
+        self.normalize_cpus(utilization.busy * self.online as f64)
+    }
+
+    /// Returns `used` CPUs as a share of the available capacity.
+    pub fn normalize_cpus(&self, used: f64) -> f64 {
+        if self.cpus > 0.0 {
+            used / self.cpus
+        } else {
+            0.0
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{CpuLimit, EffectiveCpus};
+    use crate::CpuUtilization;
+
+    #[test]
+    fn test_from_limits() {
+        let cpus = EffectiveCpus::from_limits(8, None, 8, None);
+        assert_eq!(cpus.cpus, 8.0);
+        assert_eq!(cpus.limit, CpuLimit::Online);
+
+        let cpus = EffectiveCpus::from_limits(8, Some(4), 4, None);
+        assert_eq!(cpus.cpus, 4.0);
+        assert_eq!(cpus.limit, CpuLimit::Cpuset);
+
+        let cpus = EffectiveCpus::from_limits(8, Some(4), 4, Some(2.0));
+        assert_eq!(cpus.cpus, 2.0);
+        assert_eq!(cpus.limit, CpuLimit::Quota);
+
+        let utilization = CpuUtilization {
+            busy: 0.25,
+            ..CpuUtilization::default()
+        };
+        assert_eq!(cpus.normalize(&utilization), 1.0);
+    }
+}
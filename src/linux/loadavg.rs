@@ -0,0 +1,85 @@
+use std::io::BufRead;
+use std::str::FromStr;
+
+use super::parse::Fields;
+use crate::{Error, Result};
+
+/// System load from /proc/loadavg.
+#[derive(Debug, Copy, Clone, Default, PartialEq)]
+pub struct LoadAvg {
+    /// load average over the last minute
+    pub one: f64,
+    /// load average over the last five minutes
+    pub five: f64,
+    /// load average over the last fifteen minutes
+    pub fifteen: f64,
+    /// currently runnable threads
+    pub runnable: u64,
+    /// threads that currently exist
+    pub total: u64,
+    /// PID most recently handed out
+    pub last_pid: u32,
+}
+
+impl LoadAvg {
+    /// Parses the contents of /proc/loadavg from `fd`.
+    pub fn from_reader<R: BufRead>(mut fd: R) -> Result<LoadAvg> {
+        let mut line = String::new();
+        let _len = fd.read_line(&mut line)?;
+
+        let mut fields = Fields::new(1, &line);
+        let one = fields.require("1min")?;
+        let five = fields.require("5min")?;
+        let fifteen = fields.require("15min")?;
+
+        // runnable and total are printed together as "runnable/total"
+        let tasks = fields.next().ok_or_else(|| fields.missing("tasks"))?;
+        let (runnable, total) = tasks
+            .split_once('/')
+            .and_then(|(runnable, total)| Some((runnable.parse().ok()?, total.parse().ok()?)))
+            .ok_or_else(|| fields.malformed(tasks))?;
+
+        let last_pid = fields.require("last_pid")?;
+
+        Ok(LoadAvg {
+            one,
+            five,
+            fifteen,
+            runnable,
+            total,
+            last_pid,
+        })
+    }
+}
+
+impl FromStr for LoadAvg {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<LoadAvg> {
+        LoadAvg::from_reader(s.as_bytes())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{Error, LoadAvg};
+
+    #[test]
+    fn test_parse_loadavg() {
+        let load: LoadAvg = "0.20 0.18 0.12 1/80 11206\n".parse().unwrap();
+        assert_eq!(load.one, 0.20);
+        assert_eq!(load.five, 0.18);
+        assert_eq!(load.fifteen, 0.12);
+        assert_eq!(load.runnable, 1);
+        assert_eq!(load.total, 80);
+        assert_eq!(load.last_pid, 11206);
+    }
+
+    #[test]
+    fn test_parse_loadavg_malformed() {
+        match "0.20 0.18 0.12 1-80 11206\n".parse::<LoadAvg>() {
+            Err(Error::Malformed { column: 4, .. }) => (),
+            other => panic!("unexpected {:?}", other),
+        }
+    }
+}
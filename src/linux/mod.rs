@@ -0,0 +1,103 @@
+use std::collections::BTreeMap;
+
+use crate::{CpuStats, Result};
+
+pub use cgroup::{Cgroup, CgroupCpuDelta, CgroupCpuStats, CgroupFs, CgroupVersion, CpuQuota};
+pub use cpufreq::{CpuFreq, TimeInState};
+pub use cpuset::CpuSet;
+pub use effective::{CpuLimit, EffectiveCpus};
+pub use loadavg::LoadAvg;
+pub use numa::{node_utilization, NumaNode};
+pub use pressure::{Pressure, PressureDelta, PressureLine};
+pub use proc_stat::{Interrupts, ProcStat, SoftIrqs};
+pub use process::ProcessStat;
+pub use procfs::ProcFs;
+pub use sysfs::SysFs;
+pub use thread::{ThreadDelta, ThreadStat, ThreadUsage};
+pub use topology::{CoreUtilization, CpuTopology, Topology};
+
+mod affinity;
+mod cgroup;
+mod cpufreq;
+mod cpuset;
+mod effective;
+mod loadavg;
+mod numa;
+mod parse;
+mod pressure;
+mod proc_stat;
+mod process;
+mod procfs;
+mod sysfs;
+mod thread;
+mod topology;
+
+/// Reads and parses the whole of /proc/stat.
+pub fn read_proc_stat() -> Result<ProcStat> {
+    ProcFs::default().stat()
+}
+
+pub fn read_proc_stat_cpu() -> Result<CpuStats> {
+    ProcFs::default().cpu_stats()
+}
+
+/// Returns statistics for each online CPU keyed by the kernel CPU id.
+///
+/// Offline CPUs have no `cpuN` line, so the ids may have gaps.
+pub fn read_proc_stat_per_cpu() -> Result<BTreeMap<usize, CpuStats>> {
+    ProcFs::default().cpu_stats_per_cpu()
+}
+
+/// Reads /proc/loadavg.
+pub fn read_loadavg() -> Result<LoadAvg> {
+    ProcFs::default().loadavg()
+}
+
+/// Reads CPU pressure stall information from /proc/pressure/cpu.
+pub fn read_pressure_cpu() -> Result<Pressure> {
+    ProcFs::default().pressure_cpu()
+}
+
+/// Reads the CPU accounting of the cgroup the calling process belongs to.
+///
+/// Works with both cgroup v1 and v2, whichever is mounted.
+pub fn read_cgroup_cpu_stats() -> Result<CgroupCpuStats> {
+    let procfs = ProcFs::default();
+    CgroupFs::detect(&procfs)?.current(&procfs)?.cpu_stats()
+}
+
+/// Returns the CPU capacity available to the calling thread.
+pub fn read_effective_cpus() -> Result<EffectiveCpus> {
+    EffectiveCpus::detect(&ProcFs::default())
+}
+
+/// Reads the CPU accounting of process `pid` from /proc/[pid]/stat.
+pub fn read_process_stat(pid: u32) -> Result<ProcessStat> {
+    ProcFs::default().process_stat(pid)
+}
+
+/// Lists the threads of process `pid` with their CPU accounting.
+pub fn read_threads(pid: u32) -> Result<Vec<ThreadStat>> {
+    ProcFs::default().threads(pid)
+}
+
+/// Reads the frequency scaling state of every CPU that has a cpufreq
+/// driver, keyed by the kernel CPU id.
+pub fn read_cpufreq_per_cpu() -> Result<BTreeMap<usize, CpuFreq>> {
+    SysFs::default().cpufreq_per_cpu()
+}
+
+/// Reads the package, core and thread layout of the online CPUs.
+pub fn read_topology() -> Result<Topology> {
+    SysFs::default().topology()
+}
+
+/// Reads the CPUs and distances of the online NUMA nodes.
+pub fn read_numa_nodes() -> Result<BTreeMap<usize, NumaNode>> {
+    SysFs::default().numa_nodes()
+}
+
+/// Reads the CPUs that are currently online.
+pub fn read_online_cpus() -> Result<CpuSet> {
+    SysFs::default().online_cpus()
+}
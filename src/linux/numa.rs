@@ -0,0 +1,79 @@
+use std::collections::BTreeMap;
+use std::ops::Add;
+
+use crate::{CpuDelta, CpuSet, CpuUtilization};
+
+/// A NUMA node from `devices/system/node/nodeN` in sysfs.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct NumaNode {
+    /// CPUs local to the node, empty for memory-only nodes
+    pub cpus: CpuSet,
+    /// relative access distance to each node by node id, 10 being local
+    pub distances: BTreeMap<usize, u32>,
+}
+
+impl NumaNode {
+    /// Whether the node has memory but no CPUs, as with CXL or
+    /// persistent memory.
+    pub fn is_memory_only(&self) -> bool {
+        self.cpus.is_empty()
+    }
+
+    /// Sums per-CPU stats or deltas of the node's CPUs.
+    ///
+    /// Returns `None` if none of them are in `per_cpu`, which is always the
+    /// case for memory-only nodes.
+    pub fn sum<T>(&self, per_cpu: &BTreeMap<usize, T>) -> Option<T>
+    where
+        T: Copy + Add<Output = T>,
+    {
+        self.cpus.sum(per_cpu)
+    }
+
+    /// Utilization of the node's CPUs over an interval.
+    pub fn utilization(&self, per_cpu: &BTreeMap<usize, CpuDelta>) -> Option<CpuUtilization> {
+        self.sum(per_cpu).map(|delta| delta.utilization())
+    }
+}
+
+/// Utilization of each NUMA node with CPUs, keyed by node id.
+pub fn node_utilization(
+    nodes: &BTreeMap<usize, NumaNode>,
+    per_cpu: &BTreeMap<usize, CpuDelta>,
+) -> BTreeMap<usize, CpuUtilization> {
+    nodes
+        .iter()
+        .filter_map(|(&id, node)| Some((id, node.utilization(per_cpu)?)))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use super::{node_utilization, NumaNode};
+    use crate::test_util::delta;
+
+    #[test]
+    fn test_node_utilization() {
+        let nodes = BTreeMap::from([
+            (
+                0,
+                NumaNode {
+                    cpus: "0-1".parse().unwrap(),
+                    ..NumaNode::default()
+                },
+            ),
+            (1, NumaNode::default()),
+        ]);
+        let deltas = BTreeMap::from([(0, delta(3, 7)), (1, delta(1, 9)), (2, delta(10, 0))]);
+
+        assert!(nodes[&1].is_memory_only());
+        assert_eq!(nodes[&1].sum(&deltas), None);
+        assert_eq!(nodes[&0].sum(&deltas), Some(delta(4, 16)));
+
+        let util = node_utilization(&nodes, &deltas);
+        assert_eq!(util.keys().copied().collect::<Vec<_>>(), vec![0]);
+        assert_eq!(util[&0].busy, 0.2);
+    }
+}
@@ -0,0 +1,107 @@
+use std::fs::File;
+use std::io::{self, BufReader};
+use std::path::Path;
+use std::str::{FromStr, SplitAsciiWhitespace};
+use std::time::Duration;
+
+use crate::clock_ticks::ticks_to_duration;
+use crate::{Error, Result};
+
+/// Whitespace separated fields of one line, keeping track of the position
+/// for error reporting.
+pub(crate) struct Fields<'a> {
+    line: usize,
+    column: usize,
+    inner: SplitAsciiWhitespace<'a>,
+}
+
+impl<'a> Fields<'a> {
+    pub(crate) fn new(line: usize, s: &'a str) -> Self {
+        Fields {
+            line,
+            column: 0,
+            inner: s.split_ascii_whitespace(),
+        }
+    }
+
+    /// Counts columns from `column` onwards, for lines where the first
+    /// fields were consumed by other means.
+    pub(crate) fn starting_at(mut self, column: usize) -> Self {
+        self.column = column - 1;
+        self
+    }
+
+    /// Skips over `n` fields.
+    pub(crate) fn skip_fields(&mut self, n: usize) {
+        for _ in 0..n {
+            self.next();
+        }
+    }
+
+    /// Parses the next field, or returns `None` at the end of the line.
+    pub(crate) fn parse<T: FromStr>(&mut self) -> Result<Option<T>> {
+        match self.next() {
+            Some(token) => token.parse().map(Some).map_err(|_| self.malformed(token)),
+            None => Ok(None),
+        }
+    }
+
+    /// Parses the next field, which must be present.
+    pub(crate) fn require<T: FromStr>(&mut self, field: &'static str) -> Result<T> {
+        self.parse()?.ok_or_else(|| self.missing(field))
+    }
+
+    /// Parses the next field as clock ticks, or returns `None` at the end of
+    /// the line.
+    pub(crate) fn parse_ticks(&mut self) -> Result<Option<Duration>> {
+        self.parse()?.map(ticks_to_duration).transpose()
+    }
+
+    /// Parses the next field as clock ticks, which must be present.
+    pub(crate) fn require_ticks(&mut self, field: &'static str) -> Result<Duration> {
+        ticks_to_duration(self.require(field)?)
+    }
+
+    /// Error for `token` found at the current column.
+    pub(crate) fn malformed(&self, token: &str) -> Error {
+        Error::Malformed {
+            line: self.line,
+            column: self.column,
+            token: token.to_owned(),
+        }
+    }
+
+    /// Error for `field` missing at the next column.
+    pub(crate) fn missing(&self, field: &'static str) -> Error {
+        Error::MissingField {
+            line: self.line,
+            column: self.column + 1,
+            field,
+        }
+    }
+}
+
+impl<'a> Iterator for Fields<'a> {
+    type Item = &'a str;
+
+    fn next(&mut self) -> Option<&'a str> {
+        let token = self.inner.next()?;
+        self.column += 1;
+        Some(token)
+    }
+}
+
+/// Opens a file for parsing.
+pub(crate) fn open(path: impl AsRef<Path>) -> Result<BufReader<File>> {
+    Ok(BufReader::new(File::open(path)?))
+}
+
+/// Maps a missing file to `None`, for files that only some kernels or
+/// configurations provide.
+pub(crate) fn not_found_as_none<T>(result: Result<T>) -> Result<Option<T>> {
+    match result {
+        Ok(value) => Ok(Some(value)),
+        Err(Error::Io(err)) if err.kind() == io::ErrorKind::NotFound => Ok(None),
+        Err(err) => Err(err),
+    }
+}
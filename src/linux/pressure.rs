@@ -0,0 +1,206 @@
+use std::io::BufRead;
+use std::ops::Sub;
+use std::str::FromStr;
+use std::time::Duration;
+
+use super::parse::Fields;
+use crate::delta::{sub, sub_opt};
+use crate::{Error, Result};
+
+/// Pressure stall information from /proc/pressure/cpu.
+#[derive(Debug, Copy, Clone, Default, PartialEq)]
+pub struct Pressure {
+    /// at least some runnable tasks were waiting for a CPU
+    pub some: PressureLine,
+    /// all non-idle tasks were waiting at the same time (Linux 5.13+ for
+    /// CPU, and only in cgroups before that)
+    pub full: Option<PressureLine>,
+}
+
+/// One line of a pressure file.
+#[derive(Debug, Copy, Clone, Default, PartialEq)]
+pub struct PressureLine {
+    /// percentage of time stalled over the last 10 seconds
+    pub avg10: f64,
+    /// percentage of time stalled over the last 60 seconds
+    pub avg60: f64,
+    /// percentage of time stalled over the last 300 seconds
+    pub avg300: f64,
+    /// total time stalled
+    pub total: Duration,
+}
+
+/// Stall time between two `Pressure` snapshots.
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq)]
+pub struct PressureDelta {
+    pub some: Duration,
+    pub full: Option<Duration>,
+}
+
+impl Pressure {
+    /// Parses the contents of a pressure file from `fd`.
+    pub fn from_reader<R: BufRead>(fd: R) -> Result<Pressure> {
+        let mut some = None;
+        let mut full = None;
+
+        for (i, line) in fd.lines().enumerate() {
+            let line = line?;
+            let mut fields = Fields::new(i + 1, &line);
+
+            match fields.next() {
+                Some("some") => some = Some(parse_pressure_fields(&mut fields)?),
+                Some("full") => full = Some(parse_pressure_fields(&mut fields)?),
+                Some(token) => return Err(fields.malformed(token)),
+                None => continue,
+            }
+        }
+
+        let some = some.ok_or(Error::MissingField {
+            line: 1,
+            column: 1,
+            field: "some",
+        })?;
+
+        Ok(Pressure { some, full })
+    }
+}
+
+impl FromStr for Pressure {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Pressure> {
+        Pressure::from_reader(s.as_bytes())
+    }
+}
+
+impl PressureDelta {
+    /// Computes `later - earlier`.
+    ///
+    /// Returns `Error::CounterRegression` if a total in `later` is smaller
+    /// than in `earlier`.
+    pub fn between(earlier: &Pressure, later: &Pressure) -> Result<PressureDelta> {
+        let some = sub("some", later.some.total, earlier.some.total)?;
+        let full = sub_opt(
+            "full",
+            later.full.map(|full| full.total),
+            earlier.full.map(|full| full.total),
+        )?;
+
+        Ok(PressureDelta { some, full })
+    }
+
+    /// Fraction of `elapsed` wall time during which some tasks were stalled.
+    pub fn some_rate(&self, elapsed: Duration) -> f64 {
+        rate(self.some, elapsed)
+    }
+
+    /// Fraction of `elapsed` wall time during which all tasks were stalled.
+    pub fn full_rate(&self, elapsed: Duration) -> Option<f64> {
+        self.full.map(|full| rate(full, elapsed))
+    }
+}
+
+impl Sub for Pressure {
+    type Output = Result<PressureDelta>;
+
+    fn sub(self, earlier: Pressure) -> Result<PressureDelta> {
+        PressureDelta::between(&earlier, &self)
+    }
+}
+
+// avg10=0.00 avg60=0.00 avg300=0.00 total=0
+fn parse_pressure_fields(fields: &mut Fields<'_>) -> Result<PressureLine> {
+    let (mut avg10, mut avg60, mut avg300, mut total) = (None, None, None, None);
+
+    while let Some(token) = fields.next() {
+        let (key, value) = token
+            .split_once('=')
+            .ok_or_else(|| fields.malformed(token))?;
+
+        match key {
+            "avg10" => avg10 = Some(value.parse().map_err(|_| fields.malformed(token))?),
+            "avg60" => avg60 = Some(value.parse().map_err(|_| fields.malformed(token))?),
+            "avg300" => avg300 = Some(value.parse().map_err(|_| fields.malformed(token))?),
+            "total" => {
+                let micros = value.parse().map_err(|_| fields.malformed(token))?;
+                total = Some(Duration::from_micros(micros));
+            }
+            _ => (),
+        }
+    }
+
+    // A truncated line would otherwise read as zero and later look like a
+    // counter regression.
+    Ok(PressureLine {
+        avg10: avg10.ok_or_else(|| fields.missing("avg10"))?,
+        avg60: avg60.ok_or_else(|| fields.missing("avg60"))?,
+        avg300: avg300.ok_or_else(|| fields.missing("avg300"))?,
+        total: total.ok_or_else(|| fields.missing("total"))?,
+    })
+}
+
+fn rate(stalled: Duration, elapsed: Duration) -> f64 {
+    if elapsed.is_zero() {
+        0.0
+    } else {
+        stalled.as_secs_f64() / elapsed.as_secs_f64()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use crate::{Error, Pressure};
+
+    #[test]
+    fn test_parse_pressure() {
+        let earlier: Pressure = "some avg10=1.50 avg60=0.75 avg300=0.25 total=1000000\n\
+                                 full avg10=0.00 avg60=0.00 avg300=0.00 total=0\n"
+            .parse()
+            .unwrap();
+        assert_eq!(earlier.some.avg10, 1.5);
+        assert_eq!(earlier.some.total, Duration::from_secs(1));
+        assert_eq!(earlier.full.unwrap().total, Duration::ZERO);
+
+        let later: Pressure = "some avg10=1.50 avg60=0.75 avg300=0.25 total=1500000\n\
+                               full avg10=0.00 avg60=0.00 avg300=0.00 total=250000\n"
+            .parse()
+            .unwrap();
+        let delta = (later - earlier).unwrap();
+        assert_eq!(delta.some, Duration::from_millis(500));
+        assert_eq!(delta.some_rate(Duration::from_secs(2)), 0.25);
+        assert_eq!(delta.full_rate(Duration::from_secs(1)), Some(0.25));
+    }
+
+    #[test]
+    fn test_parse_pressure_without_full() {
+        let pressure: Pressure = "some avg10=0.00 avg60=0.00 avg300=0.00 total=42\n"
+            .parse()
+            .unwrap();
+        assert_eq!(pressure.some.total, Duration::from_micros(42));
+        assert_eq!(pressure.full, None);
+    }
+
+    #[test]
+    fn test_parse_pressure_malformed() {
+        match "some avg10=x avg60=0.00 avg300=0.00 total=0\n".parse::<Pressure>() {
+            Err(Error::Malformed {
+                line: 1, column: 2, ..
+            }) => (),
+            other => panic!("unexpected {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_parse_pressure_missing_key() {
+        match "some avg10=0.00 avg60=0.00 avg300=0.00\n".parse::<Pressure>() {
+            Err(Error::MissingField {
+                line: 1,
+                field: "total",
+                ..
+            }) => (),
+            other => panic!("unexpected {:?}", other),
+        }
+    }
+}
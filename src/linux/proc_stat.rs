@@ -0,0 +1,263 @@
+use std::collections::BTreeMap;
+use std::io::BufRead;
+use std::str::FromStr;
+use std::time::{Duration, SystemTime};
+
+use super::parse::Fields;
+use crate::{CpuStats, Error, Result};
+
+/// Snapshot of the counters in /proc/stat.
+///
+/// Counters the running kernel does not report are left at zero.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ProcStat {
+    /// statistics summed over all CPUs
+    pub cpu: CpuStats,
+    /// statistics for each online CPU keyed by the kernel CPU id
+    pub cpus: BTreeMap<usize, CpuStats>,
+    /// context switches across all CPUs
+    pub ctxt: u64,
+    /// boot time in seconds since the Unix epoch
+    pub btime: u64,
+    /// processes and threads created since boot
+    pub processes: u64,
+    /// threads in runnable state
+    pub procs_running: u64,
+    /// threads blocked waiting for I/O to complete
+    pub procs_blocked: u64,
+    /// interrupts serviced since boot
+    pub intr: Interrupts,
+    /// softirqs serviced since boot
+    pub softirq: SoftIrqs,
+}
+
+/// Interrupt counts from the `intr` line of /proc/stat.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Interrupts {
+    /// all interrupts, including unnumbered architecture specific ones
+    pub total: u64,
+    /// count for each numbered IRQ, indexed by IRQ number
+    pub per_irq: Vec<u64>,
+}
+
+/// Softirq counts from the `softirq` line of /proc/stat.
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq)]
+pub struct SoftIrqs {
+    /// all softirqs
+    pub total: u64,
+    /// high priority tasklets
+    pub hi: u64,
+    /// timer wheel
+    pub timer: u64,
+    /// network transmit
+    pub net_tx: u64,
+    /// network receive
+    pub net_rx: u64,
+    /// block device completions
+    pub block: u64,
+    /// I/O polling (BLOCK_IOPOLL on older kernels)
+    pub irq_poll: u64,
+    /// normal priority tasklets
+    pub tasklet: u64,
+    /// scheduler load balancing
+    pub sched: u64,
+    /// high resolution timers
+    pub hrtimer: u64,
+    /// RCU callbacks
+    pub rcu: u64,
+}
+
+impl ProcStat {
+    /// Parses the contents of /proc/stat from `fd`.
+    pub fn from_reader<R: BufRead>(fd: R) -> Result<ProcStat> {
+        let mut stat = ProcStat::default();
+
+        for (i, line) in fd.lines().enumerate() {
+            let line = line?;
+            let mut fields = Fields::new(i + 1, &line);
+
+            let key = match fields.next() {
+                Some(key) => key,
+                None => continue,
+            };
+
+            match key {
+                "cpu" => stat.cpu = parse_cpu_fields(&mut fields)?,
+                "ctxt" => stat.ctxt = fields.require("ctxt")?,
+                "btime" => stat.btime = fields.require("btime")?,
+                "processes" => stat.processes = fields.require("processes")?,
+                "procs_running" => stat.procs_running = fields.require("procs_running")?,
+                "procs_blocked" => stat.procs_blocked = fields.require("procs_blocked")?,
+                "intr" => stat.intr = parse_intr_fields(&mut fields)?,
+                "softirq" => stat.softirq = parse_softirq_fields(&mut fields)?,
+                _ => {
+                    if let Some(id) = key.strip_prefix("cpu").and_then(|id| id.parse().ok()) {
+                        stat.cpus.insert(id, parse_cpu_fields(&mut fields)?);
+                    }
+                }
+            }
+        }
+
+        Ok(stat)
+    }
+
+    /// Returns `btime` as a `SystemTime`.
+    pub fn boot_time(&self) -> SystemTime {
+        SystemTime::UNIX_EPOCH + Duration::from_secs(self.btime)
+    }
+}
+
+impl FromStr for ProcStat {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<ProcStat> {
+        ProcStat::from_reader(s.as_bytes())
+    }
+}
+
+// https://www.linuxhowtos.org/System/procstat.htm
+//
+// Columns were added over time, so older kernels print fewer of them:
+// user nice system idle [iowait [irq softirq [steal [guest [guest_nice]]]]]
+pub(crate) fn parse_cpu_fields(fields: &mut Fields<'_>) -> Result<CpuStats> {
+    Ok(CpuStats {
+        user: fields.require_ticks("user")?,
+        nice: fields.require_ticks("nice")?,
+        system: fields.require_ticks("system")?,
+        idle: fields.require_ticks("idle")?,
+        iowait: fields.parse_ticks()?,
+        irq: fields.parse_ticks()?,
+        softirq: fields.parse_ticks()?,
+        steal: fields.parse_ticks()?,
+        guest: fields.parse_ticks()?,
+        guest_nice: fields.parse_ticks()?,
+    })
+}
+
+fn parse_intr_fields(fields: &mut Fields<'_>) -> Result<Interrupts> {
+    let total = fields.require("total")?;
+
+    let mut per_irq = Vec::new();
+    while let Some(count) = fields.parse()? {
+        per_irq.push(count);
+    }
+
+    Ok(Interrupts { total, per_irq })
+}
+
+// Older kernels may lack the later classes, which are then left at zero.
+fn parse_softirq_fields(fields: &mut Fields<'_>) -> Result<SoftIrqs> {
+    Ok(SoftIrqs {
+        total: fields.require("total")?,
+        hi: fields.parse()?.unwrap_or_default(),
+        timer: fields.parse()?.unwrap_or_default(),
+        net_tx: fields.parse()?.unwrap_or_default(),
+        net_rx: fields.parse()?.unwrap_or_default(),
+        block: fields.parse()?.unwrap_or_default(),
+        irq_poll: fields.parse()?.unwrap_or_default(),
+        tasklet: fields.parse()?.unwrap_or_default(),
+        sched: fields.parse()?.unwrap_or_default(),
+        hrtimer: fields.parse()?.unwrap_or_default(),
+        rcu: fields.parse()?.unwrap_or_default(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::parse_cpu_fields;
+    use crate::linux::parse::Fields;
+    use crate::test_util::ticks;
+    use crate::{CpuStats, Error, ProcStat, Result};
+
+    fn parse_cpu_line(line: &str) -> Result<CpuStats> {
+        let mut fields = Fields::new(1, line);
+        fields.next();
+        parse_cpu_fields(&mut fields)
+    }
+
+    #[test]
+    fn test_parse_cpu_line() {
+        let stats = parse_cpu_line("cpu  10 20 30 40 50 60 70 80 9 1\n").unwrap();
+        assert_eq!(stats.user, ticks(10));
+        assert_eq!(stats.nice, ticks(20));
+        assert_eq!(stats.system, ticks(30));
+        assert_eq!(stats.idle, ticks(40));
+        assert_eq!(stats.iowait, Some(ticks(50)));
+        assert_eq!(stats.irq, Some(ticks(60)));
+        assert_eq!(stats.softirq, Some(ticks(70)));
+        assert_eq!(stats.steal, Some(ticks(80)));
+        assert_eq!(stats.guest, Some(ticks(9)));
+        assert_eq!(stats.guest_nice, Some(ticks(1)));
+        assert_eq!(stats.total(), ticks(360));
+        assert_eq!(stats.user_without_guest(), ticks(1));
+    }
+
+    #[test]
+    fn test_parse_cpu_line_old_kernel() {
+        let stats = parse_cpu_line("cpu  10 20 30 40 50\n").unwrap();
+        assert_eq!(stats.idle, ticks(40));
+        assert_eq!(stats.iowait, Some(ticks(50)));
+        assert_eq!(stats.irq, None);
+        assert_eq!(stats.guest, None);
+        assert_eq!(stats.total(), ticks(150));
+    }
+
+    #[test]
+    fn test_parse_per_cpu_with_gaps() {
+        let input = "cpu  30 0 30 30\n\
+                     cpu0 10 0 10 10\n\
+                     cpu2 20 0 20 20\n\
+                     intr 0\n";
+        let cpus = input.parse::<ProcStat>().unwrap().cpus;
+        assert_eq!(cpus.keys().copied().collect::<Vec<_>>(), vec![0, 2]);
+        assert_eq!(cpus[&2].user, ticks(20));
+    }
+
+    #[test]
+    fn test_parse_proc_stat() {
+        let input = "cpu  2326 0 415 3657 204 0 0 184 0 0\n\
+                     cpu0 2326 0 415 3657 204 0 0 184 0 0\n\
+                     intr 13552 0 0 0\n\
+                     ctxt 123456\n\
+                     btime 1700000000\n\
+                     processes 4321\n\
+                     procs_running 3\n\
+                     procs_blocked 1\n\
+                     softirq 100 0 50 0 0 50 0 0 0 0 0\n";
+        let stat = ProcStat::from_reader(input.as_bytes()).unwrap();
+        assert_eq!(stat.cpu.user, ticks(2326));
+        assert_eq!(stat.cpus.len(), 1);
+        assert_eq!(stat.ctxt, 123456);
+        assert_eq!(stat.btime, 1700000000);
+        assert_eq!(stat.processes, 4321);
+        assert_eq!(stat.procs_running, 3);
+        assert_eq!(stat.procs_blocked, 1);
+        assert_eq!(stat.intr.total, 13552);
+        assert_eq!(stat.intr.per_irq, vec![0, 0, 0]);
+        assert_eq!(stat.softirq.total, 100);
+        assert_eq!(stat.softirq.timer, 50);
+        assert_eq!(stat.softirq.block, 50);
+        assert_eq!(stat.softirq.rcu, 0);
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        match parse_cpu_line("cpu  10 20 30\n") {
+            Err(Error::MissingField {
+                line: 1,
+                column: 5,
+                field: "idle",
+            }) => (),
+            other => panic!("unexpected {:?}", other),
+        }
+
+        match "cpu 1 2 3 4\nctxt 12x\n".parse::<ProcStat>() {
+            Err(Error::Malformed {
+                line: 2,
+                column: 2,
+                token,
+            }) => assert_eq!(token, "12x"),
+            other => panic!("unexpected {:?}", other),
+        }
+    }
+}
@@ -0,0 +1,189 @@
+use std::io::BufRead;
+use std::str::FromStr;
+use std::time::{Duration, SystemTime};
+
+use super::parse::Fields;
+use crate::clock_ticks::ticks_to_duration;
+use crate::{Error, Result};
+
+/// CPU accounting of one process from /proc/[pid]/stat.
+///
+/// Fields added to the kernel later are `None` when missing.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ProcessStat {
+    pub pid: u32,
+    /// executable name, truncated by the kernel to 15 bytes
+    ///
+    /// Any bytes that are not UTF-8 are replaced with U+FFFD.
+    pub comm: String,
+    /// R, S, D, Z, T etc.
+    pub state: char,
+    pub ppid: u32,
+    /// time scheduled in user mode, including guest time
+    pub utime: Duration,
+    /// time scheduled in kernel mode
+    pub stime: Duration,
+    /// user time of waited-for children
+    pub cutime: Duration,
+    /// kernel time of waited-for children
+    pub cstime: Duration,
+    /// scheduling priority as the kernel shows it
+    pub priority: i64,
+    /// nice value from -20 to 19
+    pub nice: i64,
+    pub num_threads: u64,
+    /// time after boot the process started
+    pub start_time: Duration,
+    /// CPU the process last ran on
+    pub processor: Option<usize>,
+    /// aggregated block I/O delays (Linux 2.6.18+)
+    pub delayacct_blkio: Option<Duration>,
+    /// time spent running a virtual CPU for a guest (Linux 2.6.24+)
+    pub guest_time: Option<Duration>,
+    /// guest time of waited-for children (Linux 2.6.24+)
+    pub cguest_time: Option<Duration>,
+}
+
+impl ProcessStat {
+    /// Parses the contents of /proc/[pid]/stat from `fd`.
+    pub fn from_reader<R: BufRead>(mut fd: R) -> Result<ProcessStat> {
+        // comm is set by the process itself and may be any bytes
+        let mut bytes = Vec::new();
+        let _len = fd.read_to_end(&mut bytes)?;
+        let line = String::from_utf8_lossy(&bytes);
+
+        // comm may contain spaces and parentheses, so it is delimited by
+        // the first "(" and the last ")" rather than by whitespace.
+        let (pid, rest) = line
+            .split_once('(')
+            .ok_or_else(|| Fields::new(1, &line).missing("comm"))?;
+        let (comm, rest) = rest.rsplit_once(')').ok_or_else(|| Error::Malformed {
+            line: 1,
+            column: 2,
+            token: rest.to_owned(),
+        })?;
+
+        let pid = Fields::new(1, pid).require("pid")?;
+        let mut fields = Fields::new(1, rest).starting_at(3);
+
+        let state = fields.next().ok_or_else(|| fields.missing("state"))?;
+        let state = state.chars().next().unwrap_or_default();
+        let ppid = fields.require("ppid")?;
+        fields.skip_fields(9);
+        let utime = fields.require_ticks("utime")?;
+        let stime = fields.require_ticks("stime")?;
+        let cutime = signed_ticks(fields.require("cutime")?)?;
+        let cstime = signed_ticks(fields.require("cstime")?)?;
+        let priority = fields.require("priority")?;
+        let nice = fields.require("nice")?;
+        let num_threads = fields.require("num_threads")?;
+        fields.skip_fields(1);
+        let start_time = fields.require_ticks("starttime")?;
+        fields.skip_fields(16);
+        let processor = fields.parse()?;
+        fields.skip_fields(2);
+        let delayacct_blkio = fields.parse_ticks()?;
+        let guest_time = fields.parse_ticks()?;
+        let cguest_time = signed_opt_ticks(fields.parse()?)?;
+
+        Ok(ProcessStat {
+            pid,
+            comm: comm.to_owned(),
+            state,
+            ppid,
+            utime,
+            stime,
+            cutime,
+            cstime,
+            priority,
+            nice,
+            num_threads,
+            start_time,
+            processor,
+            delayacct_blkio,
+            guest_time,
+            cguest_time,
+        })
+    }
+
+    /// Wall clock time the process started, given the boot time `btime`
+    /// from /proc/stat.
+    pub fn started_at(&self, btime: u64) -> SystemTime {
+        SystemTime::UNIX_EPOCH + Duration::from_secs(btime) + self.start_time
+    }
+
+    /// Total CPU time of the process itself, without children.
+    pub fn cpu_time(&self) -> Duration {
+        self.utime + self.stime
+    }
+}
+
+impl FromStr for ProcessStat {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<ProcessStat> {
+        ProcessStat::from_reader(s.as_bytes())
+    }
+}
+
+// The children's times are signed in the kernel but never negative in
+// practice.
+fn signed_ticks(ticks: i64) -> Result<Duration> {
+    ticks_to_duration(ticks.max(0) as u64)
+}
+
+fn signed_opt_ticks(ticks: Option<i64>) -> Result<Option<Duration>> {
+    ticks.map(signed_ticks).transpose()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::test_util::ticks;
+    use crate::ProcessStat;
+
+    #[test]
+    fn test_parse_process_stat() {
+        let input = "4242 (my (weird) proc) S 1 4242 4242 0 -1 4194560 1000 0 0 0 \
+                     250 100 7 3 20 0 4 0 5000 100000 200 18446744073709551615 \
+                     1 1 0 0 0 0 0 0 0 0 0 0 17 3 0 0 12 40 2 0 0 0 0 0 0 0 0\n";
+        let stat: ProcessStat = input.parse().unwrap();
+
+        assert_eq!(stat.pid, 4242);
+        assert_eq!(stat.comm, "my (weird) proc");
+        assert_eq!(stat.state, 'S');
+        assert_eq!(stat.ppid, 1);
+        assert_eq!(stat.utime, ticks(250));
+        assert_eq!(stat.stime, ticks(100));
+        assert_eq!(stat.cutime, ticks(7));
+        assert_eq!(stat.cstime, ticks(3));
+        assert_eq!(stat.priority, 20);
+        assert_eq!(stat.nice, 0);
+        assert_eq!(stat.num_threads, 4);
+        assert_eq!(stat.start_time, ticks(5000));
+        assert_eq!(stat.processor, Some(3));
+        assert_eq!(stat.delayacct_blkio, Some(ticks(12)));
+        assert_eq!(stat.guest_time, Some(ticks(40)));
+        assert_eq!(stat.cguest_time, Some(ticks(2)));
+    }
+
+    #[test]
+    fn test_parse_process_stat_non_utf8_comm() {
+        let input = b"4242 (bad\xffname) R 1 4242 4242 0 -1 4194560 0 0 0 0 \
+                      8 9 0 0 20 0 1 0 100 0 0 0 0 0 0 0 0 0 0 0 0 0 0 0\n";
+        let stat = ProcessStat::from_reader(&input[..]).unwrap();
+
+        assert_eq!(stat.comm, "bad\u{fffd}name");
+        assert_eq!(stat.utime, ticks(8));
+    }
+
+    #[test]
+    fn test_parse_process_stat_old_kernel() {
+        let input = "1 (init) S 0 1 1 0 -1 256 0 0 0 0 \
+                     5 6 0 0 20 0 1 0 10 0 0 0 0 0 0 0 0 0 0 0 0 0 0 0\n";
+        let stat: ProcessStat = input.parse().unwrap();
+
+        assert_eq!(stat.start_time, ticks(10));
+        assert_eq!(stat.processor, None);
+        assert_eq!(stat.guest_time, None);
+    }
+}
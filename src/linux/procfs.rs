@@ -0,0 +1,219 @@
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::io::{self, BufRead, BufReader};
+use std::path::{Path, PathBuf};
+
+use super::parse::{self, Fields};
+use super::proc_stat::parse_cpu_fields;
+use crate::{CpuStats, Error, LoadAvg, Pressure, ProcStat, ProcessStat, Result, ThreadStat};
+
+/// A procfs mount to read counters from.
+///
+/// Defaults to `/proc`. Point it elsewhere to read the host's counters from
+/// inside a container (e.g. `/host/proc`) or to read a captured copy.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ProcFs {
+    root: PathBuf,
+}
+
+impl Default for ProcFs {
+    fn default() -> Self {
+        ProcFs::new("/proc")
+    }
+}
+
+impl ProcFs {
+    pub fn new(root: impl Into<PathBuf>) -> Self {
+        ProcFs { root: root.into() }
+    }
+
+    /// Returns the directory this procfs is read from.
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    /// Reads and parses the whole of `stat`.
+    pub fn stat(&self) -> Result<ProcStat> {
+        ProcStat::from_reader(self.open("stat")?)
+    }
+
+    /// Reads the aggregate `cpu` line of `stat`.
+    pub fn cpu_stats(&self) -> Result<CpuStats> {
+        let mut fd = self.open("stat")?;
+
+        let mut line = String::new();
+        let _len = fd.read_line(&mut line)?;
+
+        let mut fields = Fields::new(1, &line);
+        match fields.next() {
+            Some("cpu") => parse_cpu_fields(&mut fields),
+            Some(token) => Err(fields.malformed(token)),
+            None => Err(fields.missing("cpu")),
+        }
+    }
+
+    /// Returns statistics for each online CPU keyed by the kernel CPU id.
+    ///
+    /// Offline CPUs have no `cpuN` line, so the ids may have gaps.
+    pub fn cpu_stats_per_cpu(&self) -> Result<BTreeMap<usize, CpuStats>> {
+        Ok(self.stat()?.cpus)
+    }
+
+    /// Reads `loadavg`.
+    pub fn loadavg(&self) -> Result<LoadAvg> {
+        LoadAvg::from_reader(self.open("loadavg")?)
+    }
+
+    /// Reads `pressure/cpu`.
+    ///
+    /// Returns `Error::PressureUnavailable` if the kernel was built or
+    /// booted without PSI.
+    pub fn pressure_cpu(&self) -> Result<Pressure> {
+        let fd = self.open("pressure/cpu").map_err(pressure_error)?;
+        Pressure::from_reader(fd).map_err(pressure_error)
+    }
+
+    /// Reads `[pid]/stat`.
+    ///
+    /// Returns `Error::ProcessGone` if the process does not exist or exits
+    /// while being read.
+    pub fn process_stat(&self, pid: u32) -> Result<ProcessStat> {
+        self.read_process_file(pid, &format!("{}/stat", pid))?
+            .parse()
+    }
+
+    /// Reads `[pid]/task/[tid]/stat` for every thread of process `pid`,
+    /// ordered by thread id.
+    ///
+    /// Threads that exit while being listed are left out. Returns
+    /// `Error::ProcessGone` if the process itself does not exist.
+    pub fn threads(&self, pid: u32) -> Result<Vec<ThreadStat>> {
+        let tasks = match std::fs::read_dir(self.root.join(format!("{}/task", pid))) {
+            Ok(tasks) => tasks,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => {
+                return Err(Error::ProcessGone(pid))
+            }
+            Err(err) => return Err(err.into()),
+        };
+
+        let mut threads = Vec::new();
+        for task in tasks {
+            let tid = match task?.file_name().to_str().and_then(|tid| tid.parse().ok()) {
+                Some(tid) => tid,
+                None => continue,
+            };
+
+            let path = format!("{}/task/{}/stat", pid, tid);
+            match self.read_process_file(tid, &path) {
+                Ok(stat) => threads.push(stat.parse::<ProcessStat>()?.into()),
+                Err(Error::ProcessGone(_)) => continue,
+                Err(err) => return Err(err),
+            }
+        }
+
+        threads.sort_by_key(|thread: &ThreadStat| thread.tid);
+        Ok(threads)
+    }
+
+    // The directory of an exited process disappears, and reading a file
+    // that was opened before the exit fails with ESRCH or returns nothing.
+    // The contents are read lossily, as thread and process names may be
+    // any bytes.
+    pub(crate) fn read_process_file(&self, pid: u32, path: &str) -> Result<String> {
+        match std::fs::read(self.root.join(path)) {
+            Ok(content) if content.is_empty() => Err(Error::ProcessGone(pid)),
+            Ok(content) => Ok(String::from_utf8_lossy(&content).into_owned()),
+            Err(err)
+                if err.kind() == io::ErrorKind::NotFound
+                    || err.raw_os_error() == Some(libc::ESRCH) =>
+            {
+                Err(Error::ProcessGone(pid))
+            }
+            Err(err) => Err(err.into()),
+        }
+    }
+
+    pub(crate) fn open(&self, path: impl AsRef<Path>) -> Result<BufReader<File>> {
+        parse::open(self.root.join(path))
+    }
+}
+
+// Without CONFIG_PSI the file is missing, and with psi=0 on the kernel
+// command line reading it fails with EOPNOTSUPP.
+fn pressure_error(err: Error) -> Error {
+    match err {
+        Error::Io(ref io)
+            if io.kind() == io::ErrorKind::NotFound
+                || io.raw_os_error() == Some(libc::EOPNOTSUPP) =>
+        {
+            Error::PressureUnavailable
+        }
+        err => err,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ProcFs;
+    use crate::Error;
+
+    fn fixture() -> ProcFs {
+        ProcFs::new(concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/proc"))
+    }
+
+    #[test]
+    fn test_fixture_stat() {
+        let procfs = fixture();
+        let stat = procfs.stat().unwrap();
+        assert_eq!(stat.cpus.len(), 4);
+        assert_eq!(stat.btime, 1700000000);
+        assert_eq!(procfs.cpu_stats().unwrap(), stat.cpu);
+    }
+
+    #[test]
+    fn test_fixture_loadavg() {
+        let load = fixture().loadavg().unwrap();
+        assert_eq!(load.one, 1.07);
+        assert_eq!(load.total, 412);
+    }
+
+    #[test]
+    fn test_fixture_pressure() {
+        let pressure = fixture().pressure_cpu().unwrap();
+        assert_eq!(pressure.some.avg10, 1.53);
+        assert!(pressure.full.is_some());
+
+        match ProcFs::new("/nonexistent").pressure_cpu() {
+            Err(Error::PressureUnavailable) => (),
+            other => panic!("unexpected {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_fixture_threads_non_utf8_name() {
+        let procfs = fixture();
+        assert_eq!(procfs.process_stat(4242).unwrap().comm, "worker");
+
+        let threads = procfs.threads(4242).unwrap();
+        assert_eq!(threads.len(), 2);
+        assert_eq!(threads[1].tid, 4243);
+        assert_eq!(threads[1].name, "bad\u{fffd}name");
+    }
+
+    #[test]
+    fn test_process_stat() {
+        let procfs = ProcFs::default();
+        let stat = procfs.process_stat(std::process::id()).unwrap();
+        assert_eq!(stat.pid, std::process::id());
+
+        let threads = procfs.threads(std::process::id()).unwrap();
+        assert!(threads
+            .iter()
+            .any(|thread| thread.tid == std::process::id()));
+
+        match procfs.process_stat(u32::MAX) {
+            Err(Error::ProcessGone(pid)) => assert_eq!(pid, u32::MAX),
+            other => panic!("unexpected {:?}", other),
+        }
+    }
+}
@@ -0,0 +1,359 @@
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::io::{self, BufReader};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+use super::parse::{self, not_found_as_none, Fields};
+use crate::{CpuFreq, CpuSet, CpuTopology, NumaNode, Result, TimeInState, Topology};
+
+/// A sysfs mount to read CPU information from.
+///
+/// Defaults to `/sys`. Like `ProcFs` it can be pointed at the host's sysfs
+/// from inside a container, or at a captured copy.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SysFs {
+    root: PathBuf,
+}
+
+impl Default for SysFs {
+    fn default() -> Self {
+        SysFs::new("/sys")
+    }
+}
+
+impl SysFs {
+    pub fn new(root: impl Into<PathBuf>) -> Self {
+        SysFs { root: root.into() }
+    }
+
+    /// Returns the directory this sysfs is read from.
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    /// Reads `devices/system/cpu/cpuN/cpufreq` of CPU `cpu`.
+    ///
+    /// Returns `None` if the CPU has no cpufreq driver, as in most virtual
+    /// machines.
+    pub fn cpufreq(&self, cpu: usize) -> Result<Option<CpuFreq>> {
+        let dir = format!("devices/system/cpu/cpu{}/cpufreq", cpu);
+        if !self.root.join(&dir).is_dir() {
+            return Ok(None);
+        }
+
+        let stats = format!("{}/stats", dir);
+        let time_in_state = not_found_as_none(self.open(format!("{}/time_in_state", stats)))?
+            .map(TimeInState::from_reader)
+            .transpose()?;
+
+        Ok(Some(CpuFreq {
+            cur: self.read_value(format!("{}/scaling_cur_freq", dir), "scaling_cur_freq")?,
+            min: self.read_value(format!("{}/scaling_min_freq", dir), "scaling_min_freq")?,
+            max: self.read_value(format!("{}/scaling_max_freq", dir), "scaling_max_freq")?,
+            cpuinfo_max: self
+                .read_value(format!("{}/cpuinfo_max_freq", dir), "cpuinfo_max_freq")?,
+            governor: self.read_string(format!("{}/scaling_governor", dir))?,
+            boost: self.boost(&dir)?,
+            time_in_state,
+            total_trans: not_found_as_none(
+                self.read_value(format!("{}/total_trans", stats), "total_trans"),
+            )?,
+        }))
+    }
+
+    /// Reads cpufreq of every online CPU that has a cpufreq driver, keyed by
+    /// the kernel CPU id.
+    ///
+    /// Offline CPUs are left out, as the kernel refuses to read the scaling
+    /// files of their inactive policies.
+    pub fn cpufreq_per_cpu(&self) -> Result<BTreeMap<usize, CpuFreq>> {
+        let mut cpus = BTreeMap::new();
+        for cpu in self.online_cpus()?.iter() {
+            if let Some(freq) = self.cpufreq(cpu)? {
+                cpus.insert(cpu, freq);
+            }
+        }
+        Ok(cpus)
+    }
+
+    /// Reads the CPUs that are online from `devices/system/cpu/online`.
+    pub fn online_cpus(&self) -> Result<CpuSet> {
+        self.read_string("devices/system/cpu/online")?.parse()
+    }
+
+    /// Reads the CPUs that are physically present, online or not, from
+    /// `devices/system/cpu/present`.
+    pub fn present_cpus(&self) -> Result<CpuSet> {
+        self.read_string("devices/system/cpu/present")?.parse()
+    }
+
+    /// Reads the CPUs that could ever be brought online from
+    /// `devices/system/cpu/possible`.
+    pub fn possible_cpus(&self) -> Result<CpuSet> {
+        self.read_string("devices/system/cpu/possible")?.parse()
+    }
+
+    /// Reads the CPUs isolated from the scheduler with `isolcpus=` from
+    /// `devices/system/cpu/isolated`.
+    pub fn isolated_cpus(&self) -> Result<CpuSet> {
+        self.read_string("devices/system/cpu/isolated")?.parse()
+    }
+
+    /// Reads `devices/system/cpu/cpuN/topology` of every CPU.
+    ///
+    /// Offline CPUs have no topology and are left out.
+    pub fn topology(&self) -> Result<Topology> {
+        let mut cpus = BTreeMap::new();
+        for cpu in self.cpu_ids()? {
+            let dir = format!("devices/system/cpu/cpu{}", cpu);
+            if !self.root.join(&dir).join("topology").is_dir() {
+                continue;
+            }
+
+            // Ids are -1 where the platform does not report them, which
+            // for packages means there is just one.
+            let package: i64 = self.read_value(
+                format!("{}/topology/physical_package_id", dir),
+                "physical_package_id",
+            )?;
+            let die: Option<i64> =
+                not_found_as_none(self.read_value(format!("{}/topology/die_id", dir), "die_id"))?;
+            let core: i64 = self.read_value(format!("{}/topology/core_id", dir), "core_id")?;
+            let thread_siblings = self
+                .read_string(format!("{}/topology/thread_siblings_list", dir))?
+                .parse()?;
+
+            cpus.insert(
+                cpu,
+                CpuTopology {
+                    package: usize::try_from(package).unwrap_or_default(),
+                    die: die.and_then(|die| usize::try_from(die).ok()),
+                    core: usize::try_from(core).unwrap_or_default(),
+                    thread_siblings,
+                    node: self.cpu_node(&dir)?,
+                },
+            );
+        }
+
+        Ok(Topology { cpus })
+    }
+
+    /// Reads `devices/system/node/nodeN` of every online NUMA node, keyed
+    /// by node id.
+    ///
+    /// Returns an empty map if the kernel was built without NUMA support.
+    pub fn numa_nodes(&self) -> Result<BTreeMap<usize, NumaNode>> {
+        let entries = match std::fs::read_dir(self.root.join("devices/system/node")) {
+            Ok(entries) => entries,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(BTreeMap::new()),
+            Err(err) => return Err(err.into()),
+        };
+
+        let mut ids = Vec::new();
+        for entry in entries {
+            let name = entry?.file_name();
+            if let Some(id) = name
+                .to_str()
+                .and_then(|name| name.strip_prefix("node"))
+                .and_then(|id| id.parse().ok())
+            {
+                ids.push(id);
+            }
+        }
+        ids.sort_unstable();
+
+        let mut nodes = BTreeMap::new();
+        for &id in &ids {
+            let dir = format!("devices/system/node/node{}", id);
+            let cpus = self.read_string(format!("{}/cpulist", dir))?.parse()?;
+
+            // one distance per online node, in node id order
+            let content = self.read_string(format!("{}/distance", dir))?;
+            let mut fields = Fields::new(1, &content);
+            let mut distances = BTreeMap::new();
+            for &other in &ids {
+                distances.insert(other, fields.require("distance")?);
+            }
+
+            nodes.insert(id, NumaNode { cpus, distances });
+        }
+
+        Ok(nodes)
+    }
+
+    // The CPU directory links to its NUMA node as `nodeN`.
+    fn cpu_node(&self, dir: &str) -> Result<Option<usize>> {
+        for entry in std::fs::read_dir(self.root.join(dir))? {
+            let name = entry?.file_name();
+            if let Some(node) = name
+                .to_str()
+                .and_then(|name| name.strip_prefix("node"))
+                .and_then(|id| id.parse().ok())
+            {
+                return Ok(Some(node));
+            }
+        }
+        Ok(None)
+    }
+
+    // Boost is a per-policy file on newer kernels and a global one with
+    // acpi-cpufreq. intel_pstate has its own inverted `no_turbo` switch.
+    fn boost(&self, dir: &str) -> Result<Option<bool>> {
+        let candidates = [
+            (format!("{}/boost", dir), false),
+            ("devices/system/cpu/cpufreq/boost".to_owned(), false),
+            ("devices/system/cpu/intel_pstate/no_turbo".to_owned(), true),
+        ];
+
+        for (path, inverted) in candidates {
+            if let Some(value) = not_found_as_none(self.read_value::<u8>(path, "boost"))? {
+                return Ok(Some((value != 0) != inverted));
+            }
+        }
+
+        Ok(None)
+    }
+
+    /// Ids of the CPUs listed under `devices/system/cpu`, in order.
+    ///
+    /// Includes offline CPUs that are present.
+    pub(crate) fn cpu_ids(&self) -> Result<Vec<usize>> {
+        let mut cpus = Vec::new();
+        for entry in std::fs::read_dir(self.root.join("devices/system/cpu"))? {
+            let name = entry?.file_name();
+            if let Some(cpu) = name
+                .to_str()
+                .and_then(|name| name.strip_prefix("cpu"))
+                .and_then(|id| id.parse().ok())
+            {
+                cpus.push(cpu);
+            }
+        }
+
+        cpus.sort_unstable();
+        Ok(cpus)
+    }
+
+    /// Reads a file holding a single value.
+    pub(crate) fn read_value<T: FromStr>(
+        &self,
+        path: impl AsRef<Path>,
+        field: &'static str,
+    ) -> Result<T> {
+        let content = std::fs::read_to_string(self.root.join(path))?;
+        Fields::new(1, &content).require(field)
+    }
+
+    /// Reads a file holding a single line of text, without the newline.
+    pub(crate) fn read_string(&self, path: impl AsRef<Path>) -> Result<String> {
+        let content = std::fs::read_to_string(self.root.join(path))?;
+        Ok(content.trim_end().to_owned())
+    }
+
+    pub(crate) fn open(&self, path: impl AsRef<Path>) -> Result<BufReader<File>> {
+        parse::open(self.root.join(path))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::SysFs;
+
+    fn fixture() -> SysFs {
+        SysFs::new(concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/sys"))
+    }
+
+    #[test]
+    fn test_fixture_cpufreq() {
+        let sysfs = fixture();
+        let cpus = sysfs.cpufreq_per_cpu().unwrap();
+        assert_eq!(cpus.keys().copied().collect::<Vec<_>>(), vec![0, 1]);
+
+        let cpu0 = &cpus[&0];
+        assert_eq!(cpu0.cur, 2400000);
+        assert_eq!(cpu0.cpuinfo_max, 4800000);
+        assert_eq!(cpu0.cur_fraction(), 0.5);
+        assert_eq!(cpu0.governor, "schedutil");
+        assert_eq!(cpu0.boost, Some(true));
+        assert_eq!(cpu0.time_in_state.as_ref().unwrap().states.len(), 3);
+        assert_eq!(cpu0.total_trans, Some(1234));
+
+        // no stats directory, per-policy boost wins over the global one
+        let cpu1 = &cpus[&1];
+        assert_eq!(cpu1.boost, Some(false));
+        assert_eq!(cpu1.time_in_state, None);
+        assert_eq!(cpu1.total_trans, None);
+
+        assert_eq!(sysfs.cpufreq(2).unwrap(), None);
+
+        // offline, with the scaling files of its inactive policy unreadable
+        assert!(sysfs.cpufreq(3).is_err());
+    }
+
+    #[test]
+    fn test_fixture_cpu_lists() {
+        let sysfs = fixture();
+        assert_eq!(sysfs.online_cpus().unwrap().to_string(), "0-2");
+        assert_eq!(sysfs.present_cpus().unwrap().to_string(), "0-3");
+        assert_eq!(sysfs.possible_cpus().unwrap().to_string(), "0-7");
+        assert!(sysfs.isolated_cpus().unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_cpu_lists() {
+        let sysfs = SysFs::default();
+        let online = sysfs.online_cpus().unwrap();
+        assert!(!online.is_empty());
+        assert!(online.is_subset(&sysfs.possible_cpus().unwrap()));
+    }
+
+    #[test]
+    fn test_fixture_topology() {
+        let topology = fixture().topology().unwrap();
+        assert_eq!(topology.cpus.len(), 3);
+
+        let cpu2 = &topology.cpus[&2];
+        assert_eq!(cpu2.package, 0);
+        assert_eq!(cpu2.die, Some(0));
+        assert_eq!(cpu2.thread_siblings.to_string(), "0,2");
+        assert_eq!(cpu2.core_key(), 0);
+        assert_eq!(cpu2.node, Some(0));
+
+        // an older kernel without die_id
+        let cpu1 = &topology.cpus[&1];
+        assert_eq!(cpu1.package, 1);
+        assert_eq!(cpu1.die, None);
+        assert_eq!(cpu1.node, Some(1));
+    }
+
+    #[test]
+    fn test_fixture_numa_nodes() {
+        let nodes = fixture().numa_nodes().unwrap();
+        assert_eq!(nodes.keys().copied().collect::<Vec<_>>(), vec![0, 1, 2]);
+        assert_eq!(nodes[&0].cpus.to_string(), "0,2");
+        assert_eq!(nodes[&1].distances[&0], 21);
+        assert!(nodes[&2].is_memory_only());
+
+        assert!(SysFs::new("/nonexistent").numa_nodes().unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_numa_nodes() {
+        let nodes = SysFs::default().numa_nodes().unwrap();
+        assert!(nodes
+            .values()
+            .all(|node| node.distances.len() == nodes.len()));
+    }
+
+    #[test]
+    fn test_topology() {
+        let topology = SysFs::default().topology().unwrap();
+        assert!(!topology.cpus.is_empty());
+    }
+
+    #[test]
+    fn test_cpufreq() {
+        SysFs::default().cpufreq_per_cpu().unwrap();
+    }
+}
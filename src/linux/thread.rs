@@ -0,0 +1,147 @@
+use std::collections::BTreeMap;
+use std::time::Duration;
+
+use crate::ProcessStat;
+
+/// CPU accounting of one thread from /proc/[pid]/task/[tid]/stat.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ThreadStat {
+    pub tid: u32,
+    /// thread name, truncated by the kernel to 15 bytes
+    pub name: String,
+    /// time scheduled in user mode
+    pub utime: Duration,
+    /// time scheduled in kernel mode
+    pub stime: Duration,
+    /// CPU the thread last ran on
+    pub processor: Option<usize>,
+    /// time after boot the thread started, used to tell reused ids apart
+    pub start_time: Duration,
+}
+
+/// CPU time a thread used between two samples.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ThreadUsage {
+    pub tid: u32,
+    pub name: String,
+    pub utime: Duration,
+    pub stime: Duration,
+    /// CPU the thread last ran on in the later sample
+    pub processor: Option<usize>,
+    /// share of one CPU used over the interval
+    pub utilization: f64,
+}
+
+/// Per-thread CPU usage between two samples of a process's threads.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ThreadDelta {
+    /// threads present in the later sample, busiest first
+    pub threads: Vec<ThreadUsage>,
+    /// threads that appeared after the earlier sample
+    pub started: Vec<u32>,
+    /// threads that exited before the later sample
+    pub exited: Vec<u32>,
+}
+
+impl From<ProcessStat> for ThreadStat {
+    fn from(stat: ProcessStat) -> Self {
+        ThreadStat {
+            tid: stat.pid,
+            name: stat.comm,
+            utime: stat.utime,
+            stime: stat.stime,
+            processor: stat.processor,
+            start_time: stat.start_time,
+        }
+    }
+}
+
+impl ThreadDelta {
+    /// Compares two samples taken `elapsed` apart.
+    ///
+    /// A thread that appeared in between is charged all of its CPU time,
+    /// which it can only have used after the earlier sample. An id reused
+    /// by a new thread is treated as one thread exiting and another
+    /// starting.
+    pub fn between(earlier: &[ThreadStat], later: &[ThreadStat], elapsed: Duration) -> ThreadDelta {
+        let mut before: BTreeMap<u32, &ThreadStat> =
+            earlier.iter().map(|thread| (thread.tid, thread)).collect();
+
+        let mut delta = ThreadDelta::default();
+
+        for thread in later {
+            let previous = before
+                .remove(&thread.tid)
+                .filter(|previous| previous.start_time == thread.start_time);
+
+            let (utime, stime) = match previous {
+                Some(previous) => (
+                    thread.utime.saturating_sub(previous.utime),
+                    thread.stime.saturating_sub(previous.stime),
+                ),
+                None => {
+                    if earlier.iter().any(|previous| previous.tid == thread.tid) {
+                        delta.exited.push(thread.tid);
+                    }
+                    delta.started.push(thread.tid);
+                    (thread.utime, thread.stime)
+                }
+            };
+
+            let utilization = if elapsed.is_zero() {
+                0.0
+            } else {
+                (utime + stime).as_secs_f64() / elapsed.as_secs_f64()
+            };
+
+            delta.threads.push(ThreadUsage {
+                tid: thread.tid,
+                name: thread.name.clone(),
+                utime,
+                stime,
+                processor: thread.processor,
+                utilization,
+            });
+        }
+
+        delta.exited.extend(before.keys());
+        delta.exited.sort_unstable();
+        delta
+            .threads
+            .sort_by(|a, b| b.utilization.total_cmp(&a.utilization));
+
+        delta
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::{ThreadDelta, ThreadStat};
+
+    fn thread(tid: u32, utime: u64, start_time: u64) -> ThreadStat {
+        ThreadStat {
+            tid,
+            name: format!("worker-{}", tid),
+            utime: Duration::from_millis(utime),
+            start_time: Duration::from_secs(start_time),
+            ..ThreadStat::default()
+        }
+    }
+
+    #[test]
+    fn test_thread_delta() {
+        let earlier = [thread(1, 100, 0), thread(2, 100, 0), thread(3, 100, 0)];
+        let later = [thread(1, 600, 0), thread(3, 50, 5), thread(4, 250, 6)];
+
+        let delta = ThreadDelta::between(&earlier, &later, Duration::from_secs(1));
+
+        let tids: Vec<u32> = delta.threads.iter().map(|thread| thread.tid).collect();
+        assert_eq!(tids, vec![1, 4, 3]);
+        assert_eq!(delta.threads[0].utilization, 0.5);
+        assert_eq!(delta.threads[1].utime, Duration::from_millis(250));
+        assert_eq!(delta.started, vec![3, 4]);
+        assert_eq!(delta.exited, vec![2, 3]);
+    }
+}
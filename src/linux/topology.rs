@@ -0,0 +1,184 @@
+use std::collections::BTreeMap;
+use std::ops::Add;
+
+use crate::{CpuDelta, CpuSet, CpuUtilization};
+
+/// Where one CPU sits in the system, from `cpuN/topology` in sysfs.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct CpuTopology {
+    /// physical package (socket) id
+    pub package: usize,
+    /// die id within the package, if the kernel reports dies
+    pub die: Option<usize>,
+    /// core id, only unique within a package and die
+    pub core: usize,
+    /// hardware threads sharing the core with this CPU, including itself
+    pub thread_siblings: CpuSet,
+    /// NUMA node the CPU belongs to, if the kernel has NUMA support
+    pub node: Option<usize>,
+}
+
+impl CpuTopology {
+    /// Identifies the core by its lowest numbered hardware thread, which
+    /// unlike `core` is unique across the system.
+    pub fn core_key(&self) -> usize {
+        self.thread_siblings.first().unwrap_or_default()
+    }
+}
+
+/// Layout of the online CPUs, keyed by the kernel CPU id.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Topology {
+    pub cpus: BTreeMap<usize, CpuTopology>,
+}
+
+/// Utilization of one physical core over an interval.
+///
+/// A core is busy whenever any of its hardware threads is. The counters do
+/// not tell how much the busy periods of the threads overlapped, so that
+/// share is given as bounds: `busy_min` if they overlapped completely and
+/// `busy_max` if they did not overlap at all.
+#[derive(Debug, Copy, Clone, Default, PartialEq)]
+pub struct CoreUtilization {
+    /// utilization of the summed time of all hardware threads, as if they
+    /// were independent CPUs
+    pub threads: CpuUtilization,
+    /// busy share of the busiest thread
+    pub busy_min: f64,
+    /// sum of the busy shares of all threads, at most 1.0
+    pub busy_max: f64,
+}
+
+impl Topology {
+    /// Sums per-CPU stats or deltas of the hardware threads of each core,
+    /// keyed by `CpuTopology::core_key()`.
+    ///
+    /// CPUs missing from the topology are left out.
+    pub fn by_core<T>(&self, per_cpu: &BTreeMap<usize, T>) -> BTreeMap<usize, T>
+    where
+        T: Copy + Add<Output = T>,
+    {
+        self.roll_up(per_cpu, |cpu| Some(cpu.core_key()))
+    }
+
+    /// Sums per-CPU stats or deltas of each physical package.
+    pub fn by_package<T>(&self, per_cpu: &BTreeMap<usize, T>) -> BTreeMap<usize, T>
+    where
+        T: Copy + Add<Output = T>,
+    {
+        self.roll_up(per_cpu, |cpu| Some(cpu.package))
+    }
+
+    /// Sums per-CPU stats or deltas of each NUMA node.
+    ///
+    /// Without NUMA support in the kernel the result is empty.
+    pub fn by_node<T>(&self, per_cpu: &BTreeMap<usize, T>) -> BTreeMap<usize, T>
+    where
+        T: Copy + Add<Output = T>,
+    {
+        self.roll_up(per_cpu, |cpu| cpu.node)
+    }
+
+    /// SMT-aware utilization of each core, keyed by
+    /// `CpuTopology::core_key()`.
+    pub fn core_utilization(
+        &self,
+        per_cpu: &BTreeMap<usize, CpuDelta>,
+    ) -> BTreeMap<usize, CoreUtilization> {
+        let mut busy: BTreeMap<usize, (f64, f64)> = BTreeMap::new();
+        for (id, delta) in per_cpu {
+            if let Some(cpu) = self.cpus.get(id) {
+                let thread = delta.utilization().busy;
+                let (max, sum) = busy.entry(cpu.core_key()).or_default();
+                *max = max.max(thread);
+                *sum += thread;
+            }
+        }
+
+        self.by_core(per_cpu)
+            .into_iter()
+            .map(|(core, delta)| {
+                let (max, sum) = busy[&core];
+                let utilization = CoreUtilization {
+                    threads: delta.utilization(),
+                    busy_min: max,
+                    busy_max: sum.min(1.0),
+                };
+                (core, utilization)
+            })
+            .collect()
+    }
+
+    fn roll_up<T>(
+        &self,
+        per_cpu: &BTreeMap<usize, T>,
+        key: impl Fn(&CpuTopology) -> Option<usize>,
+    ) -> BTreeMap<usize, T>
+    where
+        T: Copy + Add<Output = T>,
+    {
+        let mut groups: BTreeMap<usize, T> = BTreeMap::new();
+        for (id, &value) in per_cpu {
+            let group = match self.cpus.get(id).and_then(&key) {
+                Some(group) => group,
+                None => continue,
+            };
+            groups
+                .entry(group)
+                .and_modify(|sum| *sum = *sum + value)
+                .or_insert(value);
+        }
+        groups
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use super::{CpuTopology, Topology};
+    use crate::test_util::delta;
+
+    fn topology() -> Topology {
+        // two packages with one core of two threads each
+        let cpu = |package, core, thread_siblings: &[usize]| CpuTopology {
+            package,
+            die: Some(0),
+            core,
+            thread_siblings: thread_siblings.iter().copied().collect(),
+            node: Some(package),
+        };
+
+        Topology {
+            cpus: BTreeMap::from([
+                (0, cpu(0, 0, &[0, 2])),
+                (1, cpu(1, 0, &[1, 3])),
+                (2, cpu(0, 0, &[0, 2])),
+                (3, cpu(1, 0, &[1, 3])),
+            ]),
+        }
+    }
+
+    #[test]
+    fn test_roll_up() {
+        let topology = topology();
+        let deltas = BTreeMap::from([
+            (0, delta(4, 6)),
+            (1, delta(0, 10)),
+            (2, delta(1, 9)),
+            (3, delta(0, 10)),
+        ]);
+
+        let packages = topology.by_package(&deltas);
+        assert_eq!(packages[&0], delta(5, 15));
+        assert_eq!(packages[&1], delta(0, 20));
+        assert_eq!(topology.by_node(&deltas), packages);
+
+        let cores = topology.core_utilization(&deltas);
+        assert_eq!(cores.keys().copied().collect::<Vec<_>>(), vec![0, 1]);
+        assert_eq!(cores[&0].threads.busy, 0.25);
+        assert_eq!(cores[&0].busy_min, 0.4);
+        assert_eq!(cores[&0].busy_max, 0.5);
+        assert_eq!(cores[&1].busy_max, 0.0);
+    }
+}
@@ -0,0 +1,111 @@
+use std::collections::BTreeMap;
+use std::io;
+use std::mem::MaybeUninit;
+
+use crate::clock_ticks::ticks_to_duration;
+use crate::{CpuStats, Result};
+
+pub fn cpu_stats() -> Result<CpuStats> {
+    let host_port = get_host_port();
+    let processor_info = get_host_processor_info(host_port)?;
+    deallocate_host_port(host_port)?;
+
+    let mut user_total: usize = 0;
+    let mut system_total: usize = 0;
+    let mut idle_total: usize = 0;
+    let mut nice_total: usize = 0;
+
+    for (user, system, idle, nice) in processor_info {
+        user_total += user;
+        system_total += system;
+        idle_total += idle;
+        nice_total += nice;
+    }
+
+    to_cpu_stats(user_total, system_total, idle_total, nice_total)
+}
+
+/// Returns statistics for each CPU keyed by CPU number.
+pub fn cpu_stats_per_cpu() -> Result<BTreeMap<usize, CpuStats>> {
+    let host_port = get_host_port();
+    let processor_info = get_host_processor_info(host_port)?;
+    deallocate_host_port(host_port)?;
+
+    processor_info
+        .into_iter()
+        .enumerate()
+        .map(|(id, (user, system, idle, nice))| Ok((id, to_cpu_stats(user, system, idle, nice)?)))
+        .collect()
+}
+
+fn to_cpu_stats(user: usize, system: usize, idle: usize, nice: usize) -> Result<CpuStats> {
+    Ok(CpuStats {
+        user: ticks_to_duration(user as u64)?,
+        nice: ticks_to_duration(nice as u64)?,
+        system: ticks_to_duration(system as u64)?,
+        idle: ticks_to_duration(idle as u64)?,
+        ..CpuStats::default()
+    })
+}
+
+fn get_host_port() -> libc::mach_port_t {
+    unsafe { libc::mach_host_self() }
+}
+
+fn deallocate_host_port(name: libc::mach_port_t) -> io::Result<()> {
+    let ret = unsafe { mach2::mach_port::mach_port_deallocate(libc::mach_task_self(), name) };
+    if ret == -1 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+fn get_host_processor_info(
+    host: libc::mach_port_t,
+) -> io::Result<Vec<(usize, usize, usize, usize)>> {
+    let mut cpu_count: libc::natural_t = 0;
+    let mut cpu_info: MaybeUninit<libc::processor_info_array_t> = MaybeUninit::uninit();
+    let mut cpu_info_count = 0;
+
+    let ret = unsafe {
+        libc::host_processor_info(
+            host,
+            2,
+            &mut cpu_count,
+            cpu_info.as_mut_ptr(),
+            &mut cpu_info_count,
+        )
+    };
+
+    if ret == -1 {
+        return Err(io::Error::last_os_error());
+    }
+
+    let cpu_info = unsafe { cpu_info.assume_init() };
+
+    let cpu_info_slice = unsafe { std::slice::from_raw_parts(cpu_info, cpu_info_count as usize) };
+
+    let mut array = Vec::new();
+    for chunk in cpu_info_slice.chunks(4) {
+        array.push((
+            chunk[0] as usize,
+            chunk[1] as usize,
+            chunk[2] as usize,
+            chunk[3] as usize,
+        ));
+    }
+
+    let ret = unsafe {
+        libc::vm_deallocate(
+            libc::mach_task_self(),
+            cpu_info as libc::vm_address_t,
+            cpu_info_count as libc::vm_size_t,
+        )
+    };
+
+    if ret == -1 {
+        return Err(io::Error::last_os_error());
+    }
+
+    Ok(array)
+}
@@ -0,0 +1,127 @@
+use std::io;
+use std::mem::MaybeUninit;
+use std::time::Duration;
+
+use crate::Result;
+
+/// Whose resource usage `resource_usage()` reports.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum UsageOf {
+    /// the calling process, all threads included
+    Process,
+    /// terminated and waited-for children of the calling process
+    Children,
+    /// the calling thread
+    #[cfg(target_os = "linux")]
+    Thread,
+}
+
+/// CPU accounting from getrusage(2).
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq)]
+pub struct ResourceUsage {
+    /// time spent in user mode
+    pub user: Duration,
+    /// time spent in kernel mode
+    pub system: Duration,
+    /// context switches made by giving up the CPU voluntarily
+    pub voluntary_switches: u64,
+    /// context switches forced by the scheduler
+    pub involuntary_switches: u64,
+}
+
+impl ResourceUsage {
+    /// Total CPU time in user and kernel mode.
+    pub fn total(&self) -> Duration {
+        self.user + self.system
+    }
+
+    pub(crate) fn from_rusage(usage: &libc::rusage) -> Self {
+        ResourceUsage {
+            user: timeval_to_duration(&usage.ru_utime),
+            system: timeval_to_duration(&usage.ru_stime),
+            voluntary_switches: usage.ru_nvcsw as u64,
+            involuntary_switches: usage.ru_nivcsw as u64,
+        }
+    }
+}
+
+/// Returns the resource usage of `who` with microsecond precision.
+pub fn resource_usage(who: UsageOf) -> Result<ResourceUsage> {
+    let who = match who {
+        UsageOf::Process => libc::RUSAGE_SELF,
+        UsageOf::Children => libc::RUSAGE_CHILDREN,
+        #[cfg(target_os = "linux")]
+        UsageOf::Thread => libc::RUSAGE_THREAD,
+    };
+
+    let mut usage = MaybeUninit::<libc::rusage>::uninit();
+    let ret = unsafe { libc::getrusage(who, usage.as_mut_ptr()) };
+    if ret == -1 {
+        return Err(io::Error::last_os_error().into());
+    }
+
+    let usage = unsafe { usage.assume_init() };
+    Ok(ResourceUsage::from_rusage(&usage))
+}
+
+/// Returns the CPU time consumed by the calling process with nanosecond
+/// precision.
+///
+/// User and kernel time are not reported separately.
+pub fn process_cpu_time() -> Result<Duration> {
+    clock_gettime(libc::CLOCK_PROCESS_CPUTIME_ID)
+}
+
+/// Returns the CPU time consumed by the calling thread with nanosecond
+/// precision.
+///
+/// User and kernel time are not reported separately.
+pub fn thread_cpu_time() -> Result<Duration> {
+    clock_gettime(libc::CLOCK_THREAD_CPUTIME_ID)
+}
+
+fn clock_gettime(clock: libc::clockid_t) -> Result<Duration> {
+    let mut ts = MaybeUninit::<libc::timespec>::uninit();
+    let ret = unsafe { libc::clock_gettime(clock, ts.as_mut_ptr()) };
+    if ret == -1 {
+        return Err(io::Error::last_os_error().into());
+    }
+
+    let ts = unsafe { ts.assume_init() };
+    Ok(Duration::new(ts.tv_sec as u64, ts.tv_nsec as u32))
+}
+
+fn timeval_to_duration(tv: &libc::timeval) -> Duration {
+    Duration::new(tv.tv_sec as u64, tv.tv_usec as u32 * 1000)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{process_cpu_time, resource_usage, thread_cpu_time, UsageOf};
+    use crate::test_util::spin;
+
+    #[test]
+    fn test_cpu_time_clocks() {
+        let process = process_cpu_time().unwrap();
+        let thread = thread_cpu_time().unwrap();
+        spin();
+        assert!(process_cpu_time().unwrap() > process);
+        assert!(thread_cpu_time().unwrap() > thread);
+    }
+
+    #[test]
+    fn test_resource_usage() {
+        spin();
+        let usage = resource_usage(UsageOf::Process).unwrap();
+        assert!(!usage.total().is_zero());
+        resource_usage(UsageOf::Children).unwrap();
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_thread_resource_usage() {
+        let thread = resource_usage(UsageOf::Thread).unwrap();
+        let process = resource_usage(UsageOf::Process).unwrap();
+        assert!(thread.total() <= process.total());
+    }
+}
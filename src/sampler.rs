@@ -0,0 +1,280 @@
+use std::collections::VecDeque;
+use std::sync::mpsc::{self, RecvTimeoutError, Sender};
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+use crate::sync;
+use crate::{CpuDelta, CpuStats, Error, Result, SanitizePolicy};
+
+/// One snapshot taken by a `Sampler`.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Sample {
+    /// when the snapshot was taken
+    pub time: Instant,
+    pub stats: CpuStats,
+    /// change since the previous sample, sanitized with the default
+    /// `SanitizePolicy`; `None` for the first sample or if the counters
+    /// went backwards too far to be clamped
+    pub delta: Option<CpuDelta>,
+}
+
+/// Samples CPU statistics on a background thread at a fixed interval.
+///
+/// The last `capacity` samples are kept and can be queried through any
+/// number of `SamplerHandle`s. The thread stops when the `Sampler` is
+/// dropped.
+///
+/// Only the aggregate counters are sampled. To follow individual CPUs,
+/// including ones going offline or coming online, take snapshots with
+/// `cpu_stats_per_cpu()` and compare consecutive ones with
+/// `PerCpuDelta::between()`.
+#[derive(Debug)]
+pub struct Sampler {
+    handle: SamplerHandle,
+    stop: Option<Sender<()>>,
+    thread: Option<JoinHandle<()>>,
+}
+
+/// Cheaply cloneable, thread-safe view of the history kept by a `Sampler`.
+#[derive(Debug, Clone)]
+pub struct SamplerHandle {
+    history: Arc<Mutex<VecDeque<Sample>>>,
+}
+
+impl Sampler {
+    /// Starts sampling `cpu_stats()` every `interval`, keeping at most
+    /// `capacity` samples.
+    pub fn new(interval: Duration, capacity: usize) -> Result<Sampler> {
+        Sampler::with_source(interval, capacity, crate::cpu_stats)
+    }
+
+    /// Starts sampling `source` every `interval`, keeping at most `capacity`
+    /// samples.
+    ///
+    /// The first sample is taken before returning so that a failing source
+    /// is reported here. Later failures are skipped.
+    ///
+    /// A zero `interval` or `capacity` is rejected with
+    /// `Error::InvalidArgument`.
+    pub fn with_source<F>(interval: Duration, capacity: usize, mut source: F) -> Result<Sampler>
+    where
+        F: FnMut() -> Result<CpuStats> + Send + 'static,
+    {
+        if interval.is_zero() {
+            let why = "sampling interval must not be zero";
+            return Err(Error::InvalidArgument(why));
+        }
+        if capacity == 0 {
+            let why = "sampler capacity must not be zero";
+            return Err(Error::InvalidArgument(why));
+        }
+
+        let handle = SamplerHandle {
+            history: Arc::new(Mutex::new(VecDeque::with_capacity(capacity))),
+        };
+
+        let first = source()?;
+        handle.push(first, capacity);
+
+        let (stop, stopped) = mpsc::channel();
+        let history = handle.clone();
+        let thread = thread::spawn(move || {
+            while let Err(RecvTimeoutError::Timeout) = stopped.recv_timeout(interval) {
+                if let Ok(stats) = source() {
+                    history.push(stats, capacity);
+                }
+            }
+        });
+
+        Ok(Sampler {
+            handle,
+            stop: Some(stop),
+            thread: Some(thread),
+        })
+    }
+
+    /// Returns a handle for querying the collected samples.
+    pub fn handle(&self) -> SamplerHandle {
+        self.handle.clone()
+    }
+}
+
+impl Drop for Sampler {
+    fn drop(&mut self) {
+        // Dropping the sender wakes up and stops the sampling thread.
+        drop(self.stop.take());
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+impl SamplerHandle {
+    /// Returns the most recent sample.
+    pub fn latest(&self) -> Option<Sample> {
+        self.lock().back().copied()
+    }
+
+    /// Returns all kept samples, oldest first.
+    pub fn samples(&self) -> Vec<Sample> {
+        self.lock().iter().copied().collect()
+    }
+
+    /// Returns the change over the most recent interval.
+    pub fn current(&self) -> Option<CpuDelta> {
+        self.latest()?.delta
+    }
+
+    /// Returns the change over the samples taken within `window` of the
+    /// most recent one.
+    ///
+    /// Returns `None` if the delta of a sample in the window was dropped,
+    /// as the sum would be missing that interval.
+    pub fn over(&self, window: Duration) -> Option<CpuDelta> {
+        let history = self.lock();
+        let latest = history.back()?.time;
+
+        // Only the oldest kept sample may lack a delta, by being the first.
+        let mut sum: Option<CpuDelta> = None;
+        for (i, sample) in history.iter().enumerate().rev() {
+            if latest.duration_since(sample.time) >= window {
+                break;
+            }
+            match sample.delta {
+                Some(delta) => sum = Some(sum.map_or(delta, |sum| sum + delta)),
+                None if i == 0 => {}
+                None => return None,
+            }
+        }
+        sum
+    }
+
+    /// Returns the change over the last minute.
+    pub fn last_minute(&self) -> Option<CpuDelta> {
+        self.over(Duration::from_secs(60))
+    }
+
+    /// Returns the change over the last five minutes.
+    pub fn last_five_minutes(&self) -> Option<CpuDelta> {
+        self.over(Duration::from_secs(5 * 60))
+    }
+
+    fn push(&self, stats: CpuStats, capacity: usize) {
+        let mut history = self.lock();
+
+        let delta = history.back().and_then(|previous| {
+            SanitizePolicy::default()
+                .sanitize(&previous.stats, &stats)
+                .delta
+        });
+
+        if history.len() >= capacity {
+            history.pop_front();
+        }
+
+        history.push_back(Sample {
+            time: Instant::now(),
+            stats,
+            delta,
+        });
+    }
+
+    fn lock(&self) -> MutexGuard<'_, VecDeque<Sample>> {
+        sync::lock(&self.history)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::VecDeque;
+    use std::sync::{Arc, Mutex};
+    use std::thread;
+    use std::time::Duration;
+
+    use super::{Sampler, SamplerHandle};
+    use crate::{CpuStats, Error};
+
+    fn stats(ticks: u64) -> CpuStats {
+        CpuStats {
+            user: Duration::from_secs(ticks),
+            idle: Duration::from_secs(ticks),
+            ..CpuStats::default()
+        }
+    }
+
+    #[test]
+    fn test_sampler() {
+        let mut ticks = 0;
+        let sampler = Sampler::with_source(Duration::from_millis(1), 3, move || {
+            ticks += 1;
+            Ok(stats(ticks))
+        })
+        .unwrap();
+        let handle = sampler.handle();
+
+        while handle.samples().len() < 3 {
+            thread::sleep(Duration::from_millis(1));
+        }
+        drop(sampler);
+
+        let current = handle.current().unwrap();
+        assert_eq!(current.user, Duration::from_secs(1));
+        assert_eq!(current.utilization().busy, 0.5);
+    }
+
+    #[test]
+    fn test_invalid_arguments() {
+        let source = || Ok(stats(1));
+        assert!(matches!(
+            Sampler::with_source(Duration::ZERO, 3, source),
+            Err(Error::InvalidArgument(_))
+        ));
+        assert!(matches!(
+            Sampler::with_source(Duration::from_secs(1), 0, source),
+            Err(Error::InvalidArgument(_))
+        ));
+    }
+
+    #[test]
+    fn test_history() {
+        let handle = SamplerHandle {
+            history: Arc::new(Mutex::new(VecDeque::new())),
+        };
+
+        for ticks in [1, 2, 4, 8] {
+            handle.push(stats(ticks), 3);
+        }
+
+        let samples = handle.samples();
+        assert_eq!(samples.len(), 3);
+        assert_eq!(samples[0].stats, stats(2));
+        assert_eq!(handle.current().unwrap().user, Duration::from_secs(4));
+        assert_eq!(handle.last_minute().unwrap().user, Duration::from_secs(7));
+    }
+
+    #[test]
+    fn test_regression() {
+        let handle = SamplerHandle {
+            history: Arc::new(Mutex::new(VecDeque::new())),
+        };
+
+        // idle stepping back a little is clamped
+        handle.push(stats(2), 5);
+        handle.push(
+            CpuStats {
+                idle: Duration::from_millis(1900),
+                ..stats(3)
+            },
+            5,
+        );
+        assert_eq!(handle.current().unwrap().idle, Duration::ZERO);
+        assert_eq!(handle.last_minute().unwrap().user, Duration::from_secs(1));
+
+        // a large regression leaves a hole in the window
+        handle.push(stats(1), 5);
+        handle.push(stats(4), 5);
+        assert_eq!(handle.current().unwrap().user, Duration::from_secs(3));
+        assert_eq!(handle.last_minute(), None);
+    }
+}
@@ -0,0 +1,247 @@
+use std::time::Duration;
+
+use crate::delta;
+use crate::{CpuDelta, CpuStats};
+
+/// How to treat counters that went backwards between two snapshots.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct SanitizePolicy {
+    /// largest regression of a single counter that is clamped to zero;
+    /// larger ones drop the whole delta, and `Duration::ZERO` drops it on
+    /// any regression
+    pub max_clamp: Duration,
+    /// largest change of the boot time that is not taken as a reboot
+    pub btime_tolerance: Duration,
+}
+
+/// Something unexpected found between two snapshots.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Anomaly {
+    /// a single counter went backwards by `by`
+    Regression { field: &'static str, by: Duration },
+    /// the system rebooted in between, so all counters restarted from zero
+    Reset {
+        earlier_btime: u64,
+        later_btime: u64,
+    },
+    /// the counters as a whole went backwards, so they belong to a
+    /// different CPU than before, e.g. after hotplug or a VM migration
+    CpuReplaced,
+}
+
+/// The outcome of sanitizing a delta.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Sanitized {
+    /// the delta with small regressions clamped to zero, or `None` if it
+    /// was dropped
+    pub delta: Option<CpuDelta>,
+    /// what was found, empty if the counters were consistent
+    pub anomalies: Vec<Anomaly>,
+}
+
+impl Default for SanitizePolicy {
+    // Idle and iowait accounting of tickless CPUs is known to step back
+    // by a few ticks at a time, well below a second. NTP corrections of a
+    // running clock stay within a few seconds as well.
+    fn default() -> Self {
+        SanitizePolicy {
+            max_clamp: Duration::from_secs(1),
+            btime_tolerance: Duration::from_secs(5),
+        }
+    }
+}
+
+impl SanitizePolicy {
+    /// Computes `later - earlier`, classifying and handling regressions
+    /// instead of failing on them like `CpuDelta::between()`.
+    pub fn sanitize(&self, earlier: &CpuStats, later: &CpuStats) -> Sanitized {
+        if earlier.total().saturating_sub(later.total()) > self.max_clamp {
+            return Sanitized {
+                delta: None,
+                anomalies: vec![Anomaly::CpuReplaced],
+            };
+        }
+
+        let mut anomalies = Vec::new();
+        let found = &mut anomalies;
+        let delta = CpuDelta {
+            user: sub(found, "user", later.user, earlier.user),
+            nice: sub(found, "nice", later.nice, earlier.nice),
+            system: sub(found, "system", later.system, earlier.system),
+            idle: sub(found, "idle", later.idle, earlier.idle),
+            iowait: sub_opt(found, "iowait", later.iowait, earlier.iowait),
+            irq: sub_opt(found, "irq", later.irq, earlier.irq),
+            softirq: sub_opt(found, "softirq", later.softirq, earlier.softirq),
+            steal: sub_opt(found, "steal", later.steal, earlier.steal),
+            guest: sub_opt(found, "guest", later.guest, earlier.guest),
+            guest_nice: sub_opt(found, "guest_nice", later.guest_nice, earlier.guest_nice),
+        };
+
+        let drop = anomalies.iter().any(|anomaly| match *anomaly {
+            Anomaly::Regression { by, .. } => by > self.max_clamp || self.max_clamp.is_zero(),
+            _ => true,
+        });
+
+        Sanitized {
+            delta: if drop { None } else { Some(delta) },
+            anomalies,
+        }
+    }
+
+    /// Like `sanitize()`, but also uses the boot times of the snapshots, as
+    /// found in `ProcStat::btime`, to tell a reboot in between apart from a
+    /// replaced CPU.
+    ///
+    /// The kernel derives `btime` from the wall clock minus the uptime, so
+    /// it also moves a little when NTP steps the clock. Changes up to
+    /// `btime_tolerance` are therefore not taken as a reboot.
+    pub fn sanitize_with_btime(
+        &self,
+        earlier: &CpuStats,
+        earlier_btime: u64,
+        later: &CpuStats,
+        later_btime: u64,
+    ) -> Sanitized {
+        let shift = Duration::from_secs(earlier_btime.abs_diff(later_btime));
+        if shift > self.btime_tolerance {
+            return Sanitized {
+                delta: None,
+                anomalies: vec![Anomaly::Reset {
+                    earlier_btime,
+                    later_btime,
+                }],
+            };
+        }
+
+        self.sanitize(earlier, later)
+    }
+}
+
+// Clamps a regression to zero, recording it.
+fn sub(
+    anomalies: &mut Vec<Anomaly>,
+    field: &'static str,
+    later: Duration,
+    earlier: Duration,
+) -> Duration {
+    delta::sub(field, later, earlier).unwrap_or_else(|_| {
+        anomalies.push(Anomaly::Regression {
+            field,
+            by: earlier - later,
+        });
+        Duration::ZERO
+    })
+}
+
+fn sub_opt(
+    anomalies: &mut Vec<Anomaly>,
+    field: &'static str,
+    later: Option<Duration>,
+    earlier: Option<Duration>,
+) -> Option<Duration> {
+    Some(sub(anomalies, field, later?, earlier?))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::{Anomaly, SanitizePolicy};
+    use crate::CpuStats;
+
+    fn stats(user: u64, idle: u64, iowait: u64) -> CpuStats {
+        CpuStats {
+            user: Duration::from_millis(user),
+            idle: Duration::from_millis(idle),
+            iowait: Some(Duration::from_millis(iowait)),
+            ..CpuStats::default()
+        }
+    }
+
+    #[test]
+    fn test_consistent() {
+        let sanitized = SanitizePolicy::default().sanitize(&stats(10, 10, 10), &stats(20, 20, 20));
+        assert!(sanitized.anomalies.is_empty());
+        assert_eq!(sanitized.delta.unwrap().total(), Duration::from_millis(30));
+    }
+
+    #[test]
+    fn test_small_regression() {
+        let earlier = stats(1000, 1000, 1000);
+        let later = stats(2000, 2000, 990);
+        let anomaly = Anomaly::Regression {
+            field: "iowait",
+            by: Duration::from_millis(10),
+        };
+
+        let clamped = SanitizePolicy::default().sanitize(&earlier, &later);
+        assert_eq!(clamped.anomalies, vec![anomaly]);
+        assert_eq!(clamped.delta.unwrap().iowait, Some(Duration::ZERO));
+
+        let strict = SanitizePolicy {
+            max_clamp: Duration::ZERO,
+            ..SanitizePolicy::default()
+        };
+        let dropped = strict.sanitize(&earlier, &later);
+        assert_eq!(dropped.anomalies, vec![anomaly]);
+        assert_eq!(dropped.delta, None);
+    }
+
+    #[test]
+    fn test_large_regression() {
+        let sanitized =
+            SanitizePolicy::default().sanitize(&stats(1000, 1000, 5000), &stats(3000, 3000, 2000));
+        assert_eq!(sanitized.delta, None);
+        assert_eq!(sanitized.anomalies.len(), 1);
+    }
+
+    #[test]
+    fn test_cpu_replaced() {
+        let sanitized =
+            SanitizePolicy::default().sanitize(&stats(9000, 9000, 9000), &stats(10, 10, 10));
+        assert_eq!(sanitized.anomalies, vec![Anomaly::CpuReplaced]);
+        assert_eq!(sanitized.delta, None);
+    }
+
+    #[test]
+    fn test_reset() {
+        let policy = SanitizePolicy::default();
+        let sanitized =
+            policy.sanitize_with_btime(&stats(9000, 9000, 9000), 100, &stats(10, 10, 10), 200);
+        assert_eq!(
+            sanitized.anomalies,
+            vec![Anomaly::Reset {
+                earlier_btime: 100,
+                later_btime: 200
+            }]
+        );
+        assert_eq!(sanitized.delta, None);
+
+        let sanitized =
+            policy.sanitize_with_btime(&stats(10, 10, 10), 100, &stats(20, 20, 20), 100);
+        assert!(sanitized.delta.is_some());
+
+        // a reboot long enough ago that the counters are larger again
+        let sanitized =
+            policy.sanitize_with_btime(&stats(100, 100, 100), 1000, &stats(500, 500, 500), 5000);
+        assert_eq!(sanitized.anomalies.len(), 1);
+        assert_eq!(sanitized.delta, None);
+
+        // the clock was stepped by a second while the counters kept growing
+        let sanitized =
+            policy.sanitize_with_btime(&stats(10, 10, 10), 100, &stats(20, 20, 20), 101);
+        assert!(sanitized.anomalies.is_empty());
+        assert!(sanitized.delta.is_some());
+    }
+
+    #[test]
+    fn test_always_clamp() {
+        let policy = SanitizePolicy {
+            max_clamp: Duration::MAX,
+            ..SanitizePolicy::default()
+        };
+        let sanitized = policy.sanitize(&stats(9000, 9000, 9000), &stats(10, 10, 10));
+        assert_eq!(sanitized.anomalies.len(), 3);
+        assert_eq!(sanitized.delta.unwrap().total(), Duration::ZERO);
+    }
+}
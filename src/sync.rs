@@ -0,0 +1,9 @@
+use std::sync::{Mutex, MutexGuard};
+
+/// Locks `mutex` even if another thread panicked while holding it.
+///
+/// Everything shared this way is updated in a single step and never left
+/// half-written, so a poisoned lock still guards consistent data.
+pub(crate) fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    mutex.lock().unwrap_or_else(|err| err.into_inner())
+}
@@ -0,0 +1,30 @@
+#[cfg(target_os = "linux")]
+use std::time::Duration;
+
+#[cfg(target_os = "linux")]
+use crate::{clock_ticks, CpuDelta};
+
+/// Burns some CPU time on the calling thread.
+pub(crate) fn spin() -> u64 {
+    let mut x: u64 = 0;
+    for i in 0..20_000_000u64 {
+        x = std::hint::black_box(x.wrapping_add(i));
+    }
+    x
+}
+
+/// `n` clock ticks as a `Duration`.
+#[cfg(target_os = "linux")]
+pub(crate) fn ticks(n: u64) -> Duration {
+    Duration::from_secs(n) / clock_ticks().unwrap() as u32
+}
+
+/// A delta of `busy` seconds in user mode and `idle` seconds idle.
+#[cfg(target_os = "linux")]
+pub(crate) fn delta(busy: u64, idle: u64) -> CpuDelta {
+    CpuDelta {
+        user: Duration::from_secs(busy),
+        idle: Duration::from_secs(idle),
+        ..CpuDelta::default()
+    }
+}
@@ -0,0 +1,207 @@
+use std::collections::BTreeMap;
+use std::ops::{Add, AddAssign};
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::{Duration, Instant};
+
+use crate::sync;
+use crate::{resource_usage, ResourceUsage, Result, UsageOf};
+
+/// CPU and wall clock time consumed by a piece of code.
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq)]
+pub struct CpuMeasurement {
+    /// time spent in user mode
+    pub user: Duration,
+    /// time spent in kernel mode
+    pub system: Duration,
+    /// elapsed wall clock time
+    pub wall: Duration,
+}
+
+/// Measures the CPU time used between `start()` and `stop()`.
+///
+/// If created through `CpuCounters::timer()`, the measurement is also
+/// added to a named counter when stopped or dropped.
+#[derive(Debug)]
+pub struct CpuTimer {
+    of: UsageOf,
+    start: ResourceUsage,
+    wall: Instant,
+    counter: Option<(CpuCounters, String)>,
+}
+
+/// Accumulated measurements of one named counter.
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq)]
+pub struct CpuCounter {
+    pub total: CpuMeasurement,
+    /// number of measurements added
+    pub count: u64,
+}
+
+/// Named counters that `CpuTimer`s add their measurements to.
+///
+/// Clones share the same counters.
+#[derive(Debug, Clone, Default)]
+pub struct CpuCounters {
+    counters: Arc<Mutex<BTreeMap<String, CpuCounter>>>,
+}
+
+impl CpuMeasurement {
+    /// Total CPU time in user and kernel mode.
+    pub fn cpu(&self) -> Duration {
+        self.user + self.system
+    }
+
+    /// CPU time divided by wall clock time.
+    ///
+    /// Above 1.0 when measuring a process that ran on several CPUs at once.
+    pub fn ratio(&self) -> f64 {
+        if self.wall.is_zero() {
+            0.0
+        } else {
+            self.cpu().as_secs_f64() / self.wall.as_secs_f64()
+        }
+    }
+}
+
+impl Add for CpuMeasurement {
+    type Output = CpuMeasurement;
+
+    fn add(self, other: CpuMeasurement) -> CpuMeasurement {
+        CpuMeasurement {
+            user: self.user + other.user,
+            system: self.system + other.system,
+            wall: self.wall + other.wall,
+        }
+    }
+}
+
+impl AddAssign for CpuMeasurement {
+    fn add_assign(&mut self, other: CpuMeasurement) {
+        *self = *self + other;
+    }
+}
+
+impl CpuTimer {
+    /// Starts measuring the CPU time of `of`.
+    pub fn start(of: UsageOf) -> Result<CpuTimer> {
+        Ok(CpuTimer {
+            of,
+            start: resource_usage(of)?,
+            wall: Instant::now(),
+            counter: None,
+        })
+    }
+
+    /// Returns the time used so far without stopping the timer.
+    pub fn elapsed(&self) -> Result<CpuMeasurement> {
+        let now = resource_usage(self.of)?;
+        Ok(CpuMeasurement {
+            user: now.user.saturating_sub(self.start.user),
+            system: now.system.saturating_sub(self.start.system),
+            wall: self.wall.elapsed(),
+        })
+    }
+
+    /// Stops the timer and returns the time used.
+    pub fn stop(mut self) -> Result<CpuMeasurement> {
+        // Taken first so that Drop does not add the measurement again when
+        // elapsed() fails.
+        let counter = self.counter.take();
+        let measurement = self.elapsed()?;
+        if let Some((counters, name)) = counter {
+            counters.add(&name, measurement);
+        }
+        Ok(measurement)
+    }
+}
+
+impl Drop for CpuTimer {
+    fn drop(&mut self) {
+        if let Some((counters, name)) = self.counter.take() {
+            if let Ok(measurement) = self.elapsed() {
+                counters.add(&name, measurement);
+            }
+        }
+    }
+}
+
+impl CpuCounters {
+    pub fn new() -> Self {
+        CpuCounters::default()
+    }
+
+    /// Starts a timer that adds to the counter `name` when it is stopped or
+    /// dropped.
+    pub fn timer(&self, name: impl Into<String>, of: UsageOf) -> Result<CpuTimer> {
+        let mut timer = CpuTimer::start(of)?;
+        timer.counter = Some((self.clone(), name.into()));
+        Ok(timer)
+    }
+
+    /// Adds `measurement` to the counter `name`.
+    pub fn add(&self, name: &str, measurement: CpuMeasurement) {
+        let mut counters = self.lock();
+        let counter = match counters.get_mut(name) {
+            Some(counter) => counter,
+            None => counters.entry(name.to_owned()).or_default(),
+        };
+        counter.total += measurement;
+        counter.count += 1;
+    }
+
+    /// Returns the counter `name`.
+    pub fn get(&self, name: &str) -> Option<CpuCounter> {
+        self.lock().get(name).copied()
+    }
+
+    /// Returns all counters.
+    pub fn snapshot(&self) -> BTreeMap<String, CpuCounter> {
+        self.lock().clone()
+    }
+
+    fn lock(&self) -> MutexGuard<'_, BTreeMap<String, CpuCounter>> {
+        sync::lock(&self.counters)
+    }
+}
+
+/// Runs `f` and returns its result together with the CPU time of `of` it
+/// consumed.
+pub fn measure<T>(of: UsageOf, f: impl FnOnce() -> T) -> Result<(T, CpuMeasurement)> {
+    let timer = CpuTimer::start(of)?;
+    let value = f();
+    Ok((value, timer.stop()?))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{measure, CpuCounters};
+    use crate::test_util::spin;
+    use crate::UsageOf;
+
+    #[test]
+    fn test_measure() {
+        let (_, measurement) = measure(UsageOf::Process, spin).unwrap();
+        assert!(!measurement.wall.is_zero());
+        assert!(!measurement.cpu().is_zero());
+        assert!(measurement.ratio() > 0.0);
+    }
+
+    #[test]
+    fn test_counters() {
+        let counters = CpuCounters::new();
+
+        let timer = counters.timer("spin", UsageOf::Process).unwrap();
+        spin();
+        let measurement = timer.stop().unwrap();
+
+        {
+            let _guard = counters.timer("spin", UsageOf::Process).unwrap();
+            spin();
+        }
+
+        let counter = counters.get("spin").unwrap();
+        assert_eq!(counter.count, 2);
+        assert!(counter.total.wall >= measurement.wall);
+        assert!(counters.get("other").is_none());
+    }
+}